@@ -4,12 +4,13 @@ extern crate json_rpc;
 use json_rpc::{Server, Json, Error};
 use json_rpc::serialize::json::ToJson;
 use std::thread;
+use std::time::Duration;
 use std::collections::BTreeMap;
 
 fn main() {
     println!("Running Example ...");
 
-    let mut rpc_server = Server::new(); 
+    let rpc_server = Server::new(); 
     
     // Registers a Rpc Method named "Subtract" with two parameter "by Name".
     rpc_method!(rpc_server, Subtract, oper1<u64>;oper2<u64>, {                
@@ -70,7 +71,7 @@ fn main() {
         // It uses a macro for parse the String into a Struct. rpc_params : { oper1:u64, oper2:u64 }
         let rpc_params = rpc_params!(json_params, oper1<u64>;oper2<u64> );
         println!("Rpc Params en add: {:?}", rpc_params);
-        thread::sleep_ms(1000);
+        thread::sleep(Duration::from_millis(1000));
         let result = Json::U64(rpc_params.oper1 + rpc_params.oper2);
         Ok(result)
     });        
@@ -102,13 +103,17 @@ fn main() {
     // This operation is Notification. It doesn't include 'id' and doesn't return anything.
     let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Division\", \"params\":{\"oper1\":30, \"oper2\":7}}".to_string();
     new_request(&rpc_server, str_request);    
+
+    // A batch of requests. The notification inside the batch doesn't return anything.
+    let str_request = "[{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":{\"oper1\":23, \"oper2\":4}, \"id\":6},{\"jsonrpc\":\"2.0\",\"method\":\"Multiply\", \"params\":[2, 3]},{\"jsonrpc\":\"2.0\",\"method\":\"Multiply\", \"params\":[5, 6, 7], \"id\":7}]".to_string();
+    new_request(&rpc_server, str_request);
         
-    thread::sleep_ms(2000);    
+    thread::sleep(Duration::from_millis(2000));    
     println!("End Example");
 }
 
 fn new_request(rpc_server:&Server, str_request: String) {
-    rpc_server.request(str_request.clone(), move |str_response| {
+    rpc_server.request_async(str_request.clone(), move |str_response| {
         println!("Executed: \n   request  = {},\n   response = {}", str_request, str_response);
     });
 }
\ No newline at end of file
@@ -1,15 +1,12 @@
 #[macro_use(rpc_method, rpc_method_no_params, rpc_params)]
 extern crate json_rpc;
-extern crate hyper;
 
 use json_rpc::Server as RpcServer;
 use json_rpc::{Json, Error};
 use json_rpc::serialize::json::ToJson;
-use std::io::Read;
-use std::io::Write;
+use json_rpc::transport::http::HttpServer;
 use std::collections::BTreeMap;
-use hyper::Server as ServerHttp;
-use hyper::server::{Request,Response};
+use std::sync::Arc;
 
 
 fn main() {
@@ -19,21 +16,8 @@ fn main() {
 
     register_methods(&mut rpc_server);
 
-    ServerHttp::http(move |mut req:Request, mut res:Response| {
-        match req.method {
-            hyper::Post => {
-                let mut str_req = String::new();
-                req.read_to_string(&mut str_req).unwrap();
-                let mut res = res.start().unwrap();
-                match rpc_server.request(str_req) {
-                    Some(str_res) => res.write_all(str_res.as_bytes()).unwrap() ,
-                    None => (),      
-                };
-                res.end().unwrap();
-            },
-            _ => *res.status_mut() = hyper::status::StatusCode::MethodNotAllowed
-        }   
-    }).listen("127.0.0.1:8080").unwrap();
+    let http_server = HttpServer::bind("127.0.0.1:8080", Arc::new(rpc_server)).unwrap();
+    http_server.run();
 
     println!("Stopped server!");
 
@@ -0,0 +1,22 @@
+#[macro_use(rpc_method)]
+extern crate json_rpc;
+
+use json_rpc::{Server, Json, Error};
+use json_rpc::transport::framing::Framing;
+use json_rpc::transport::stdio::StdioServer;
+use std::env;
+use std::sync::Arc;
+
+// Run with "--content-length" to use Content-Length headers instead of one message per line.
+fn main() {
+    let rpc_server = Server::new();
+
+    // Registers a Rpc Method named "Subtract" with two parameter "by Name".
+    rpc_method!(rpc_server, Subtract, oper1<u64>;oper2<u64>, {
+        Ok(Json::U64(oper1 - oper2))
+    });
+
+    let mut stdio_server = StdioServer::new(Arc::new(rpc_server));
+    if env::args().any(|a| a == "--content-length") { stdio_server.set_framing(Framing::ContentLength) }
+    if let Err(e) = stdio_server.run() { eprintln!("{}", e) }
+}
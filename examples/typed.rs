@@ -0,0 +1,46 @@
+extern crate json_rpc;
+#[macro_use]
+extern crate serde;
+
+use json_rpc::{Server, Error};
+
+#[derive(Deserialize)]
+struct Operands {
+    oper1: f64,
+    oper2: f64,
+}
+
+#[derive(Serialize)]
+struct Info {
+    amount: u32,
+    price: f64,
+    description: String,
+}
+
+fn main() {
+    println!("Running Example ...");
+
+    let rpc_server = Server::new();
+
+    // Params are accepted "by Name" and "by Position".
+    rpc_server.register_typed_method("Division", |p:Operands| {
+        if p.oper2 == 0f64 {
+            Err(Error::custom(1, "Division by zero", None))
+        } else {
+            Ok(p.oper1 / p.oper2)
+        }
+    });
+
+    // The result doesn't need to implement ToJson.
+    rpc_server.register_typed_method("GetInfo", |_:()| {
+        Ok(Info { amount : 15, price: 2.33, description: "Apples".to_string() })
+    });
+
+    for str_request in &["{\"jsonrpc\":\"2.0\",\"method\":\"Division\", \"params\":{\"oper1\":30, \"oper2\":7}, \"id\":1}",
+                         "{\"jsonrpc\":\"2.0\",\"method\":\"Division\", \"params\":[23, 0], \"id\":2}",
+                         "{\"jsonrpc\":\"2.0\",\"method\":\"GetInfo\", \"id\":3}"] {
+        println!("Executed: \n   request  = {},\n   response = {}", str_request, rpc_server.request(str_request.to_string()).unwrap());
+    }
+
+    println!("End Example");
+}
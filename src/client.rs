@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use serialize::json::ToJson;
+use super::{Error, Id, Json, Request, Response};
+
+type Callback = Box<dyn FnOnce(Result<Json,Error>) + Send>;
+
+/// Builds JSON-RPC 2.0 requests and matches the responses with the pending calls.
+/// It doesn't send anything: the strings returned must be delivered by any transport
+/// and the strings received from the server must be passed to **response**.
+pub struct Client {
+    next_id: Mutex<u64>,
+    pending: Mutex<BTreeMap<u64, Callback>>,
+}
+
+impl Default for Client {
+    fn default() -> Client {
+        Client::new()
+    }
+}
+
+impl Client {
+    pub fn new() -> Client {
+        Client {
+            next_id : Mutex::new(1),
+            pending : Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Returns the request to send. **f_response** is called once the response with the same id is received.
+    pub fn request<F>(&self, method:&str, params:Json, f_response:F) -> String where F: FnOnce(Result<Json,Error>) + Send + 'static {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        self.pending.lock().unwrap().insert(id, Box::new(f_response));
+        Request::new(method, params, Id::Value(Json::U64(id))).to_json().to_string()
+    }
+
+    /// Returns a request without id. The server doesn't send any response.
+    pub fn notification(&self, method:&str, params:Json) -> String {
+        Request::new(method, params, Id::Absent).to_json().to_string()
+    }
+
+    /// Number of calls waiting for a response.
+    pub fn pending(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    /// Calls every pending callback with **error**. Transports use it when the connection is closed.
+    pub fn cancel_pending(&self, error:Error) {
+        let pending = ::std::mem::take(&mut *self.pending.lock().unwrap());
+        for (_, f) in pending { f(Err(error.clone())) }
+    }
+
+    /// Processes a response or a batch of responses received from the server.
+    /// Returns an error if the string isn't a valid response, if it doesn't match any pending call,
+    /// or if the server returned an error without id.
+    pub fn response(&self, str_response:String) -> Result<(), Error> {
+        let data = match Json::from_str(&str_response) {
+            Ok(o) => o,
+            Err(_) => return Err(Error::predefined(-32700, None))
+        };
+        match data {
+            Json::Array(ref batch) => {
+                let mut result = Ok(());
+                for r in batch {
+                    if let Err(e) = self.response_object(r) { result = Err(e) }
+                }
+                result
+            },
+            _ => self.response_object(&data)
+        }
+    }
+
+    fn response_object(&self, data:&Json) -> Result<(), Error> {
+        let response = Response::parse(data)?;
+        let id = match response.id {
+            Json::U64(id) => id,
+            Json::Null => return match response.result {
+                Err(e) => Err(e),
+                Ok(_) => Err(Error::predefined(-32600, Some(data.clone())))
+            },
+            _ => return Err(Error::predefined(-32600, Some(data.clone())))
+        };
+        let f = match self.pending.lock().unwrap().remove(&id) {
+            Some(f) => f,
+            None => return Err(Error::predefined(-32603, Some(data.clone())))
+        };
+        f(response.result);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Client;
+    use super::super::{Server, Error, Json};
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_request() {
+        let client = Client::new();
+        let (tx, rx) = mpsc::channel();
+        let str_request = client.request("Subtract", Json::from_str("[42, 23]").unwrap(), move |res| tx.send(res).unwrap());
+        assert_eq!(str_request, "{\"id\":1,\"jsonrpc\":\"2.0\",\"method\":\"Subtract\",\"params\":[42,23]}");
+        assert_eq!(client.pending(), 1);
+        client.response("{\"id\":1,\"jsonrpc\":\"2.0\",\"result\":19}".to_string()).unwrap();
+        assert_eq!(rx.recv().unwrap(), Ok(Json::U64(19)));
+        assert_eq!(client.pending(), 0);
+
+        let str_request = client.notification("Update", Json::Null);
+        assert_eq!(str_request, "{\"jsonrpc\":\"2.0\",\"method\":\"Update\"}");
+    }
+
+    #[test]
+    fn test_out_of_order_responses() {
+        let client = Client::new();
+        let (tx, rx) = mpsc::channel();
+        let tx_c = tx.clone();
+        client.request("First", Json::Null, move |res| tx_c.send((1, res)).unwrap());
+        client.request("Second", Json::Null, move |res| tx.send((2, res)).unwrap());
+        let str_response = "[{\"id\":2,\"jsonrpc\":\"2.0\",\"error\":{\"code\":-32601,\"message\":\"Method not found\"}},
+                             {\"id\":1,\"jsonrpc\":\"2.0\",\"result\":\"one\"}]".to_string();
+        client.response(str_response).unwrap();
+        let (call, res) = rx.recv().unwrap();
+        assert_eq!(call, 2);
+        let err = res.unwrap_err();
+        assert_eq!(err.code(), -32601);
+        assert_eq!(err.message(), "Method not found");
+        assert_eq!(rx.recv().unwrap(), (1, Ok(Json::String("one".to_string()))));
+    }
+
+    #[test]
+    fn test_invalid_responses() {
+        let client = Client::new();
+        assert_eq!(client.response("{".to_string()).unwrap_err().code(), -32700);
+        assert_eq!(client.response("{\"id\":1,\"result\":1}".to_string()).unwrap_err().code(), -32600);
+        assert_eq!(client.response("{\"id\":7,\"jsonrpc\":\"2.0\",\"result\":1}".to_string()).unwrap_err().code(), -32603);
+        let str_response = "{\"id\":null,\"jsonrpc\":\"2.0\",\"error\":{\"code\":-32700,\"message\":\"Parse error\"}}".to_string();
+        assert_eq!(client.response(str_response), Err(Error::predefined(-32700, None)));
+    }
+
+    #[test]
+    fn test_with_server() {
+        let rpc_server = Server::new();
+        rpc_server.register_method("Multiply", |json_params| {
+            let values = json_params.as_array().unwrap().iter().map(|v| v.as_u64().unwrap());
+            Ok(Json::U64(values.product()))
+        });
+        let client = Client::new();
+        let (tx, rx) = mpsc::channel();
+        let str_request = client.request("Multiply", Json::from_str("[5, 6, 7]").unwrap(), move |res| tx.send(res).unwrap());
+        client.response(rpc_server.request(str_request).unwrap()).unwrap();
+        assert_eq!(rx.recv().unwrap(), Ok(Json::U64(210)));
+    }
+}
@@ -0,0 +1,131 @@
+use std::any::{Any, TypeId};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use super::Id;
+
+/// Values of any type, at most one of each type.
+#[derive(Clone, Default)]
+pub struct Extensions {
+    map: BTreeMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    /// Returns the previous value of the same type.
+    pub fn insert<T>(&mut self, value:T) -> Option<Arc<T>> where T: Any + Send + Sync {
+        self.map.insert(TypeId::of::<T>(), Arc::new(value)).and_then(|v| v.downcast().ok())
+    }
+
+    pub fn get<T>(&self) -> Option<&T> where T: Any + Send + Sync {
+        self.map.get(&TypeId::of::<T>()).and_then(|v| v.downcast_ref())
+    }
+
+    pub(crate) fn get_arc<T>(&self) -> Option<Arc<T>> where T: Any + Send + Sync {
+        self.map.get(&TypeId::of::<T>()).cloned().and_then(|v| v.downcast().ok())
+    }
+
+    pub fn remove<T>(&mut self) -> Option<Arc<T>> where T: Any + Send + Sync {
+        self.map.remove(&TypeId::of::<T>()).and_then(|v| v.downcast().ok())
+    }
+
+    pub fn contains<T>(&self) -> bool where T: Any + Send + Sync {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+}
+
+/// Information about a request for the methods and the middlewares.
+/// The transports fill the metadata, as **"peer_addr"**, and can add extensions.
+/// Each call of a batch has its own copy.
+#[derive(Clone)]
+pub struct Context {
+    id: Id,
+    method: String,
+    metadata: BTreeMap<String, String>,
+    extensions: Extensions,
+}
+
+impl Default for Context {
+    fn default() -> Context {
+        Context::new()
+    }
+}
+
+impl Context {
+    pub fn new() -> Context {
+        Context {
+            id: Id::Absent,
+            method: String::new(),
+            metadata: BTreeMap::new(),
+            extensions: Extensions::default(),
+        }
+    }
+
+    /// The id of the request. **Id::Absent** for notifications.
+    pub fn id(&self) -> &Id {
+        &self.id
+    }
+
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut BTreeMap<String, String> {
+        &mut self.metadata
+    }
+
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Copy of the context for a request.
+    pub(crate) fn for_request(&self, id:&Id, method:&str) -> Context {
+        Context {
+            id: id.clone(),
+            method: method.to_string(),
+            metadata: self.metadata.clone(),
+            extensions: self.extensions.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Context, Extensions};
+    use super::super::{Id, Json};
+
+    #[derive(Debug, PartialEq)]
+    struct User(String);
+
+    #[test]
+    fn test_extensions() {
+        let mut extensions = Extensions::default();
+        assert!(extensions.insert(User("ann".to_string())).is_none());
+        assert!(extensions.insert(5u32).is_none());
+        assert_eq!(extensions.get::<User>(), Some(&User("ann".to_string())));
+        assert_eq!(*extensions.insert(6u32).unwrap(), 5);
+        assert_eq!(extensions.get::<u32>(), Some(&6));
+        assert!(extensions.get::<u64>().is_none());
+        assert_eq!(*extensions.remove::<User>().unwrap(), User("ann".to_string()));
+        assert!(!extensions.contains::<User>());
+    }
+
+    #[test]
+    fn test_for_request() {
+        let mut context = Context::new();
+        context.metadata_mut().insert("peer_addr".to_string(), "127.0.0.1:1".to_string());
+        context.extensions_mut().insert(User("ann".to_string()));
+        assert_eq!(*context.id(), Id::Absent);
+        let request_context = context.for_request(&Id::Value(Json::U64(1)), "Echo");
+        assert_eq!(*request_context.id(), Id::Value(Json::U64(1)));
+        assert_eq!(request_context.method(), "Echo");
+        assert_eq!(request_context.metadata().get("peer_addr").unwrap(), "127.0.0.1:1");
+        assert!(request_context.extensions().contains::<User>());
+    }
+}
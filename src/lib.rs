@@ -31,7 +31,7 @@ extern crate json_rpc;
 use json_rpc::{Server, Json, Error};
 
 fn main() {
-    let mut rpc_server = Server::new(); 
+    let rpc_server = Server::new(); 
 
     // Registers a Rpc Method named "Subtract" with two parameter "by Name".
     rpc_method!(rpc_server, Subtract, oper1<u64>;oper2<u64>, {                
@@ -64,12 +64,43 @@ fn main() {
 
 extern crate asynchronous;
 pub extern crate rustc_serialize as serialize;
+#[cfg(feature = "serde")]
+#[cfg_attr(test, macro_use)]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 
 use asynchronous::{Deferred, Promise};
-use std::collections::BTreeMap;
-use std::sync::Arc;
+use std::cmp;
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::any::Any;
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use task::{BoxFuture, Completion};
+use serialize::json::ToJson;
 pub use serialize::json::Json;
+pub use client::Client;
+pub use context::{Context, Extensions};
+pub use middleware::Middleware;
+pub use peer::Peer;
+pub use request::{Id, Request};
+pub use response::Response;
 
+mod client;
+mod context;
+mod middleware;
+mod peer;
+mod request;
+mod response;
+mod task;
+pub mod transport;
+#[cfg(feature = "serde")]
+pub mod typed;
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Error {
     code : i64,
     message : String,
@@ -78,193 +109,536 @@ pub struct Error {
 
 impl Error {
     pub fn custom(code:i64, message: &str, data: Option<Json>) -> Error {
-        if code >= -32768 && code <= -32000 {
+        if (-32768..=-32000).contains(&code) {
             panic!("You cannot assign a pre-defined error.");
         }
         Error {
-            code: code, message: message.to_string(), data: data
+            code, message: message.to_string(), data
         }
     }
 
     pub fn predefined(code:i64, data: Option<Json>) -> Error {
         Error {
-            code: code, 
+            code,
             message:  match code {
                 -32700 => "Parse error".to_string(),
                 -32600 => "Invalid Request".to_string(),
                 -32601 => "Method not found".to_string(),
                 -32602 => "Invalid params".to_string(),
                 -32603 => "Internal error".to_string(),
-                -32099 ... -32000 => "Server error".to_string(),
+                -32099 ..= -32000 => "Server error".to_string(),
                 _ => panic!("Predefined error code incorrect.")
-            }, 
-            data: data
+            },
+            data
         }
     }
 
+    pub fn code(&self) -> i64 {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn data(&self) -> Option<&Json> {
+        self.data.as_ref()
+    }
+
+    fn from_object(data:&Json) -> Option<Error> {
+        let obj = data.as_object()?;
+        let code = obj.get("code").and_then(|c| c.as_i64())?;
+        let message = obj.get("message").and_then(|m| m.as_string())?;
+        Some(Error { code, message: message.to_string(), data: obj.get("data").cloned() })
+    }
+
     fn as_object(&self) -> Json {
         let mut error_object = BTreeMap::new();
         error_object.insert("code".to_string(), Json::I64(self.code));
         error_object.insert("message".to_string(), Json::String(self.message.to_string()));
-        match self.data {            
-            Some(ref v) => { error_object.insert("data".to_string(), v.clone()); } ,
-            None => (),
-        }        
+        if let Some(ref v) = self.data {
+            error_object.insert("data".to_string(), v.clone());
+        }
         Json::Object(error_object)
     }
 }
 
+/// How the requests inside a batch are executed. Responses always keep the order of the requests.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BatchExecution {
+    /// Executes one request after the other, in the order of the batch.
+    Series,
+    /// Executes all requests at the same time, with a thread for each synchronous method.
+    /// The number of threads is not limited: a client can start as many as the calls of its batch.
+    Parallel,
+    /// Executes all requests but only **usize** requests running at the same time.
+    ParallelLimit(usize),
+}
+
+type SyncMethod = Arc<dyn Fn(&Context, Json) -> Result<Json,Error> + 'static + Send + Sync>;
+type NotificationErrorHook = Arc<dyn Fn(&str, &Error) + 'static + Send + Sync>;
+
+enum Handler {
+    Sync(SyncMethod),
+    Future(Box<dyn Fn(Context, Json) -> BoxFuture + 'static + Send + Sync>),
+    Deferred(Box<dyn Fn(Context, Json) -> Deferred<Json,Error> + 'static + Send + Sync>),
+}
+
+type Method = Arc<Handler>;
+
+/// A request ready to be executed, or its response if it already failed.
+/// The method is **None** if it doesn't exist, so the middlewares see the request anyway.
+enum Call {
+    Ready(Option<Response>),
+    Method(Request, Option<Method>, Context),
+}
+
+/// Settings of the server used by the calls in flight.
+#[derive(Clone)]
+struct Dispatch {
+    notification_error_hook: Option<NotificationErrorHook>,
+    middlewares: Arc<Vec<Arc<dyn Middleware>>>,
+    panic_data: bool,
+}
+
+/// Executes the calls of a batch following the **BatchExecution** of the server.
+struct Batch {
+    queue: Mutex<VecDeque<(usize, Call)>>,
+    results: Mutex<(Vec<Option<Response>>, usize)>,
+    done: Mutex<Option<Completion<Vec<Response>>>>,
+    dispatch: Dispatch,
+}
+
+impl Batch {
+    /// Executes the calls one after the other while they finish in this thread. When a call finishes
+    /// later in another thread, that thread continues with the next calls, so the stack doesn't grow.
+    fn next(batch:&Arc<Batch>) {
+        loop {
+            let (index, call) = match batch.queue.lock().unwrap().pop_front() {
+                Some(c) => c,
+                None => return
+            };
+            // The second of the call and its completion to arrive here continues with the next call
+            let handoff = Arc::new(AtomicBool::new(false));
+            let handoff_c = handoff.clone();
+            let batch_c = batch.clone();
+            Server::run(call, batch.dispatch.clone(), false, Box::new(move |response| {
+                let finished = {
+                    let mut results = batch_c.results.lock().unwrap();
+                    results.0[index] = response;
+                    results.1 -= 1;
+                    results.1 == 0
+                };
+                if finished {
+                    let responses = batch_c.results.lock().unwrap().0.drain(..).flatten().collect();
+                    if let Some(done) = batch_c.done.lock().unwrap().take() { done(responses) }
+                } else if handoff_c.swap(true, Ordering::SeqCst) {
+                    Batch::next(&batch_c)
+                }
+            }));
+            if !handoff.swap(true, Ordering::SeqCst) { return }
+        }
+    }
+}
+
+/// Requests of a batch running at the same time by default.
+const DEFAULT_BATCH_LIMIT: usize = 4;
+
 pub struct Server {
-    methods: BTreeMap<String, Arc<Box<Fn(Json) -> Result<Json,Error> + 'static + Send + Sync >>>
+    methods: RwLock<BTreeMap<String, Method>>,
+    batch_execution: BatchExecution,
+    dispatch: Dispatch,
+    strict_ids: bool,
+    state: Arc<RwLock<Extensions>>,
+    mounts: BTreeMap<String, Arc<Server>>,
+    separator: String,
+}
+
+impl Default for Server {
+    fn default() -> Server {
+        Server::new()
+    }
 }
 
 impl Server {
     pub fn new() -> Server {
         Server {
-            methods : BTreeMap::new()
+            methods : RwLock::new(BTreeMap::new()),
+            batch_execution : BatchExecution::ParallelLimit(DEFAULT_BATCH_LIMIT),
+            dispatch : Dispatch { notification_error_hook : None, middlewares : Arc::new(Vec::new()), panic_data : false },
+            strict_ids : false,
+            state : Arc::new(RwLock::new(Extensions::default())),
+            mounts : BTreeMap::new(),
+            separator : ".".to_string(),
+        }
+    }
+
+    /// By default, **ParallelLimit** with 4 requests running at the same time.
+    pub fn set_batch_execution(&mut self, batch_execution:BatchExecution) {
+        self.batch_execution = batch_execution;
+    }
+
+    /// Notifications never return a response, not even an error.
+    /// **f** receives the method name and the error of every notification that fails.
+    pub fn set_notification_error_hook<F>(&mut self, f:F) where F: Fn(&str, &Error) + 'static + Send + Sync {
+        self.dispatch.notification_error_hook = Some(Arc::new(f));
+    }
+
+    /// Rejects requests with null ids or numeric ids with fractional parts with "Invalid Request".
+    pub fn set_strict_ids(&mut self, strict_ids:bool) {
+        self.strict_ids = strict_ids;
+    }
+
+    /// A method that panics returns "Internal error". If **panic_data** is true, the panic message is sent in the **data** field.
+    pub fn set_panic_data(&mut self, panic_data:bool) {
+        self.dispatch.panic_data = panic_data;
+    }
+
+    /// Adds a middleware after the ones already added.
+    pub fn add_middleware<M>(&mut self, middleware:M) where M: Middleware + 'static {
+        Arc::make_mut(&mut self.dispatch.middlewares).push(Arc::new(middleware));
+    }
+
+    /// Separator between the prefix of a mounted server and the name of its methods. By default is ".".
+    pub fn set_separator(&mut self, separator:&str) {
+        self.separator = separator.to_string();
+    }
+
+    /// Mounts the methods of **server** with the name "prefix" + separator + method, as "user.create".
+    /// The calls use the middlewares and settings of this server. Mounting the same prefix again replaces the server.
+    pub fn mount<S>(&mut self, prefix:&str, server:S) where S: Into<Arc<Server>> {
+        self.mounts.insert(prefix.to_string(), server.into());
+    }
+
+    /// Names of all the methods, including the ones of the mounted servers, in order.
+    pub fn method_names(&self) -> Vec<String> {
+        let mut names:Vec<String> = self.methods.read().unwrap().keys().cloned().collect();
+        for (prefix, server) in self.mounts.iter() {
+            names.extend(server.method_names().into_iter().map(|n| format!("{}{}{}", prefix, self.separator, n)));
         }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Registers a method, or replaces it if it exists. As the other **register_** methods, it can be called
+    /// while the server is shared: the calls in flight finish with the previous method.
+    pub fn register_method<F>(&self, method:&str, f:F) where F: Fn(Json) -> Result<Json,Error> + 'static + Send + Sync  {
+        self.register_method_with_context(method, move |_, params| f(params));
+    }
+
+    /// Registers a method that also receives the **Context** of the request.
+    pub fn register_method_with_context<F>(&self, method:&str, f:F) where F: Fn(&Context, Json) -> Result<Json,Error> + 'static + Send + Sync  {
+        self.insert_method(method, Handler::Sync(Arc::new(f)));
+    }
+
+    /// Sets the state given to the methods registered with **register_method_with_state**, as a database pool.
+    /// There's one state of each type, and it can be replaced at any time, also while the server is shared.
+    pub fn set_state<S>(&self, state:S) where S: Any + Send + Sync {
+        self.state.write().unwrap().insert(state);
+    }
+
+    /// Registers a method that receives the state of type **S**, so it can be a plain function.
+    /// It returns "Internal error" if the state isn't set.
+    pub fn register_method_with_state<S, F>(&self, method:&str, f:F) where S: Any + Send + Sync, F: Fn(&S, Json) -> Result<Json,Error> + 'static + Send + Sync {
+        let state = self.state.clone();
+        self.register_method(method, move |params| {
+            let state = state.read().unwrap().get_arc::<S>();
+            match state {
+                Some(s) => f(&s, params),
+                None => Err(Error::predefined(-32603, Some(Json::String("State not set".to_string()))))
+            }
+        });
+    }
+
+    /// Registers a method that returns a Future. The Future is polled again by the thread that wakes it,
+    /// so there isn't any thread waiting for the result.
+    pub fn register_future_method<F, T>(&self, method:&str, f:F)
+        where F: Fn(Json) -> T + 'static + Send + Sync, T: Future<Output=Result<Json,Error>> + 'static + Send {
+        self.register_future_method_with_context(method, move |_, params| f(params));
     }
 
-    pub fn register_method<F>(&mut self, method:&str, f:F) where F: Fn(Json) -> Result<Json,Error> + 'static + Send + Sync  {
-        self.methods.insert(method.to_string(), Arc::new(Box::new(f)));
+    /// Registers a method that returns a Future and receives the **Context** of the request.
+    pub fn register_future_method_with_context<F, T>(&self, method:&str, f:F)
+        where F: Fn(Context, Json) -> T + 'static + Send + Sync, T: Future<Output=Result<Json,Error>> + 'static + Send {
+        self.insert_method(method, Handler::Future(Box::new(move |context, params| Box::pin(f(context, params)))));
+    }
+
+    /// Registers a method that returns a Deferred. The response is sent once the Deferred is resolved.
+    pub fn register_deferred_method<F>(&self, method:&str, f:F) where F: Fn(Json) -> Deferred<Json,Error> + 'static + Send + Sync {
+        self.register_deferred_method_with_context(method, move |_, params| f(params));
+    }
+
+    /// Registers a method that returns a Deferred and receives the **Context** of the request.
+    pub fn register_deferred_method_with_context<F>(&self, method:&str, f:F) where F: Fn(Context, Json) -> Deferred<Json,Error> + 'static + Send + Sync {
+        self.insert_method(method, Handler::Deferred(Box::new(f)));
+    }
+
+    /// Like **register_method**, and returns true if the method existed.
+    /// The methods of the other forms are replaced with their **register_** method.
+    pub fn replace_method<F>(&self, method:&str, f:F) -> bool where F: Fn(Json) -> Result<Json,Error> + 'static + Send + Sync {
+        self.insert_method(method, Handler::Sync(Arc::new(move |_:&Context, params| f(params)))).is_some()
+    }
+
+    /// Removes a method while the server is shared. The calls in flight finish anyway.
+    /// Returns true if the method existed. The methods of the mounted servers aren't removed.
+    pub fn unregister_method(&self, method:&str) -> bool {
+        self.methods.write().unwrap().remove(method).is_some()
+    }
+
+    /// Returns true if the method exists, in this server or in a mounted one.
+    pub fn has_method(&self, method:&str) -> bool {
+        self.find_method(method).is_some()
     }
 
     pub fn request(&self, str_request:String) -> Option<String> {
+        self.request_with_context(str_request, &Context::new())
+    }
+
+    /// Like **request**, the methods receive a copy of **context** with the id and the method of their request.
+    pub fn request_with_context(&self, str_request:String, context:&Context) -> Option<String> {
         let data = match Json::from_str(&str_request) {
             Ok(o) => o,
-            Err(_) => return Some(Server::response_error(-32700))
+            Err(_) => return Some(Server::response_error(Json::Null, -32700).to_json().to_string())
         };
-        let obj = match data.as_object() {
-            Some(s) => s,
-            None => return Some(Server::response_error(-32600))
-        };
-        match obj.get("jsonrpc") {
-            Some(o) => match o.as_string() {
-                Some(s) => if s!="2.0" { return Some(Server::response_error(-32600)) },
-                None => return Some(Server::response_error(-32600))                
-            },
-            None => return Some(Server::response_error(-32600))
-        };
-        let str_method = match obj.get("method") {
-            Some(o) => match o.as_string() {
-                Some(s) => s,
-                None => return Some(Server::response_error(-32600))                
-            },
-            None => return Some(Server::response_error(-32600))
-        };
-        let params = match obj.get("params") {
-            Some(o) => match *o {
-                Json::Array(ref v) => Json::Array(v.clone()),
-                Json::Object(ref v) => Json::Object(v.clone()),
-                _ => return Some(Server::response_error(-32600))
-            },
-            None => Json::Null
-        };
-        let id:Option<Json> = match obj.get("id") {
-            Some(o) => match *o {
-                Json::String(ref v) => Some(Json::String(v.clone())),
-                Json::I64(ref v) => Some(Json::I64(v.clone())),
-                Json::U64(ref v) => Some(Json::U64(v.clone())),
-                Json::F64(ref v) => Some(Json::F64(v.clone())),
-                Json::Null => None,
-                _ => return Some(Server::response_error(-32600))
+        let (tx, rx) = mpsc::channel();
+        match data {
+            Json::Array(ref batch) => {
+                if batch.is_empty() { return Some(Server::response_error(Json::Null, -32600).to_json().to_string()) }
+                self.execute_batch(batch, context, Box::new(move |responses| { let _ = tx.send(Server::batch_response(responses)); }));
             },
-            None => None
-        };
-        let f = match self.methods.get(str_method) {
-            Some(o) => o.clone(),
-            None => return Some(Server::response_error(-32601))
-        };        
-        if id.is_some() {
-            let res = f(params);
-            let mut resp_object = BTreeMap::new();
-            resp_object.insert("jsonrpc".to_string(), Json::String("2.0".to_string()));
-            resp_object.insert("id".to_string(), id.unwrap());                
-            match res {
-                Ok(v) => { resp_object.insert("result".to_string(), v); } ,
-                Err(e) => { resp_object.insert("error".to_string(), e.as_object()); }
-            } 
-            Some(Json::Object(resp_object).to_string())
-        } else {
-            Promise::new(move || { f(params) });
-            None
+            _ => self.execute(&data, context, true, Box::new(move |response| { let _ = tx.send(response.map(|r| r.to_json())); }))
         }
-    }    
+        rx.recv().unwrap_or(None).map(|r| r.to_string())
+    }
 
     pub fn request_async<F>(&self, str_request:String, f_response:F) where F: FnOnce(String) + Send + 'static {
+        self.request_async_with_context(str_request, &Context::new(), f_response)
+    }
+
+    /// Like **request_async**, the methods receive a copy of **context** with the id and the method of their request.
+    pub fn request_async_with_context<F>(&self, str_request:String, context:&Context, f_response:F) where F: FnOnce(String) + Send + 'static {
         let data = match Json::from_str(&str_request) {
             Ok(o) => o,
-            Err(_) => return f_response(Server::response_error(-32700))
-        };
-        let obj = match data.as_object() {
-            Some(s) => s,
-            None => return f_response(Server::response_error(-32600))
+            Err(_) => return f_response(Server::response_error(Json::Null, -32700).to_json().to_string())
         };
-        match obj.get("jsonrpc") {
-            Some(o) => match o.as_string() {
-                Some(s) => if s!="2.0" { return f_response(Server::response_error(-32600)) },
-                None => return f_response(Server::response_error(-32600))                
+        match data {
+            Json::Array(ref batch) => {
+                if batch.is_empty() { return f_response(Server::response_error(Json::Null, -32600).to_json().to_string()) }
+                self.execute_batch(batch, context, Box::new(move |responses| {
+                    if let Some(r) = Server::batch_response(responses) { f_response(r.to_string()) }
+                }));
             },
-            None => return f_response(Server::response_error(-32600))
+            _ => self.execute(&data, context, false, Box::new(move |response| {
+                if let Some(r) = response { f_response(r.to_json().to_string()) }
+            }))
+        }
+    }
+
+    /// Executes a request object. If **blocking** is true, the caller waits for the response:
+    /// synchronous methods are executed in the same thread, and notifications finish immediately.
+    fn execute(&self, data:&Json, context:&Context, blocking:bool, done:Completion<Option<Response>>) {
+        let call = self.prepare(data, context);
+        Server::run(call, self.dispatch.clone(), blocking, done)
+    }
+
+    fn execute_batch(&self, data:&[Json], context:&Context, done:Completion<Vec<Response>>) {
+        let queue:VecDeque<(usize, Call)> = data.iter().map(|r| self.prepare(r, context)).enumerate().collect();
+        let size = queue.len();
+        let limit = match self.batch_execution {
+            BatchExecution::Series => 1,
+            BatchExecution::Parallel => size,
+            BatchExecution::ParallelLimit(limit) => cmp::max(limit, 1),
         };
-        let str_method = match obj.get("method") {
-            Some(o) => match o.as_string() {
-                Some(s) => s,
-                None => return f_response(Server::response_error(-32600))                
-            },
-            None => return f_response(Server::response_error(-32600))
+        let batch = Arc::new(Batch {
+            queue: Mutex::new(queue),
+            results: Mutex::new((vec![None; size], size)),
+            done: Mutex::new(Some(done)),
+            dispatch: self.dispatch.clone(),
+        });
+        for _ in 0..limit { Batch::next(&batch) }
+    }
+
+    fn run(call:Call, dispatch:Dispatch, blocking:bool, done:Completion<Option<Response>>) {
+        let (mut request, f, mut context) = match call {
+            Call::Ready(response) => return done(response),
+            Call::Method(request, f, context) => (request, f, context)
         };
-        let params = match obj.get("params") {
-            Some(o) => match *o {
-                Json::Array(ref v) => Json::Array(v.clone()),
-                Json::Object(ref v) => Json::Object(v.clone()),
-                _ => return f_response(Server::response_error(-32600))
-            },
-            None => Json::Null
+        // Middlewares before the method, until one returns a result
+        let mut result = None;
+        let mut entered = 0;
+        for middleware in dispatch.middlewares.iter() {
+            result = match panic::catch_unwind(AssertUnwindSafe(|| middleware.before(&mut request, &mut context))) {
+                Ok(res) => res,
+                Err(payload) => Some(Err(task::panic_error(payload, dispatch.panic_data)))
+            };
+            if result.is_some() { break }
+            entered += 1;
+        }
+        let id = request.id.to_response_id();
+        let (blocking, done) = if blocking && id.is_none() {
+            done(None);
+            (false, Box::new(|_| ()) as Completion<Option<Response>>)
+        } else { (blocking, done) };
+        let params = if entered > 0 { request.params.clone() } else { mem::replace(&mut request.params, Json::Null) };
+        let Dispatch { notification_error_hook, middlewares, panic_data } = dispatch;
+        let context = Arc::new(context);
+        let context_c = context.clone();
+        let finish:Completion<Result<Json,Error>> = Box::new(move |mut res| {
+            for middleware in middlewares[..entered].iter().rev() {
+                if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| middleware.after(&request, &context_c, &mut res))) {
+                    res = Err(task::panic_error(payload, panic_data));
+                }
+            }
+            match id {
+                Some(id) => done(Some(Response::new(id, res))),
+                None => {
+                    if let Err(e) = res { Server::notification_error(&notification_error_hook, &request.method, &e) }
+                    done(None)
+                }
+            }
+        });
+        if let Some(res) = result { return finish(res) }
+        let f = match f {
+            Some(f) => f,
+            None => return finish(Err(Error::predefined(-32601, None)))
         };
-        let id:Option<Json> = match obj.get("id") {
-            Some(o) => match *o {
-                Json::String(ref v) => Some(Json::String(v.clone())),
-                Json::I64(ref v) => Some(Json::I64(v.clone())),
-                Json::U64(ref v) => Some(Json::U64(v.clone())),
-                Json::F64(ref v) => Some(Json::F64(v.clone())),
-                Json::Null => None,
-                _ => return f_response(Server::response_error(-32600))
+        match *f {
+            Handler::Sync(ref h) => {
+                if blocking { return finish(Server::invoke(h, &context, params, panic_data)) }
+                let h = h.clone();
+                Promise::<(), ()>::new(move || {
+                    finish(Server::invoke(&h, &context, params, panic_data));
+                    Ok(())
+                });
             },
-            None => None
-        };
-        let f = match self.methods.get(str_method) {
-            Some(o) => o.clone(),
-            None => return f_response(Server::response_error(-32601))
-        };
-        Deferred::new(move ||{                                        
-            f(params)
-        }).finally(move |res| {
-            if id.is_some() {
-                let mut resp_object = BTreeMap::new();
-                resp_object.insert("jsonrpc".to_string(), Json::String("2.0".to_string()));
-                resp_object.insert("id".to_string(), id.unwrap());                
-                match res {
-                    Ok(v) => { resp_object.insert("result".to_string(), v); } ,
-                    Err(e) => { resp_object.insert("error".to_string(), e.as_object()); }
-                } 
-                f_response(Json::Object(resp_object).to_string());
+            Handler::Future(ref h) => match panic::catch_unwind(AssertUnwindSafe(|| h((*context).clone(), params))) {
+                Ok(future) => task::drive(future, panic_data, finish),
+                Err(payload) => finish(Err(task::panic_error(payload, panic_data)))
+            },
+            Handler::Deferred(ref h) => match panic::catch_unwind(AssertUnwindSafe(|| h((*context).clone(), params))) {
+                Ok(deferred) => deferred.finally(finish),
+                Err(payload) => finish(Err(task::panic_error(payload, panic_data)))
             }
-        });
+        }
+    }
+
+    fn prepare(&self, data:&Json, context:&Context) -> Call {
+        let parsed = if self.strict_ids { Request::parse_strict(data) } else { Request::parse(data) };
+        let request = match parsed {
+            Ok(r) => r,
+            Err(e) => {
+                let parsed_id = if self.strict_ids { Request::parse_id_strict(data) } else { Request::parse_id(data) };
+                let id = match parsed_id {
+                    Ok(Id::Absent) => match data.find("method").and_then(|m| m.as_string()) {
+                        // Without a valid "jsonrpc" it's an invalid request rather than a notification
+                        Some(method) if data.find("jsonrpc").and_then(|v| v.as_string()) == Some("2.0") => {
+                            Server::notification_error(&self.dispatch.notification_error_hook, method, &e);
+                            return Call::Ready(None)
+                        },
+                        _ => Json::Null
+                    },
+                    Ok(id) => id.to_response_id().unwrap_or(Json::Null),
+                    Err(_) => Json::Null
+                };
+                return Call::Ready(Some(Response::new(id, Err(e))))
+            }
+        };
+        let f = self.find_method(&request.method);
+        let context = context.for_request(&request.id, &request.method);
+        Call::Method(request, f, context)
+    }
+
+    fn insert_method(&self, method:&str, handler:Handler) -> Option<Method> {
+        self.methods.write().unwrap().insert(method.to_string(), Arc::new(handler))
+    }
+
+    /// Looks for the method in this server and then in the mounted servers.
+    fn find_method(&self, method:&str) -> Option<Method> {
+        if let Some(f) = self.methods.read().unwrap().get(method) { return Some(f.clone()) }
+        self.mounts.iter().filter_map(|(prefix, server)| {
+            let name = method.strip_prefix(prefix.as_str())?.strip_prefix(self.separator.as_str())?;
+            server.find_method(name)
+        }).next()
+    }
+
+    fn invoke(f:&SyncMethod, context:&Context, params:Json, panic_data:bool) -> Result<Json,Error> {
+        match panic::catch_unwind(AssertUnwindSafe(|| f(context, params))) {
+            Ok(res) => res,
+            Err(payload) => Err(task::panic_error(payload, panic_data))
+        }
+    }
+
+    fn notification_error(hook:&Option<NotificationErrorHook>, method:&str, e:&Error) {
+        if let Some(ref hook) = *hook { hook(method, e) }
     }
 
-    fn response_error(code:i64) -> String {
-        let mut resp_object = BTreeMap::new();
-        resp_object.insert("jsonrpc".to_string(), Json::String("2.0".to_string()));        
-        resp_object.insert("error".to_string(), Error::predefined(code, None).as_object());
-        resp_object.insert("id".to_string(), Json::Null);      
-        Json::Object(resp_object).to_string()
+    fn batch_response(responses:Vec<Response>) -> Option<Json> {
+        if responses.is_empty() { return None }
+        Some(Json::Array(responses.iter().map(|r| r.to_json()).collect()))
+    }
+
+    fn response_error(id:Json, code:i64) -> Response {
+        Response::new(id, Err(Error::predefined(code, None)))
     }
 
 }
 
 #[macro_export]
 macro_rules! rpc_method {
+    ( $rpc_struct:expr, $rpc_method:expr, &$state:ident : $s:ty, $($n:ident<$t:ty>);+ , $rpc_block:block ) => {
+        $rpc_struct.register_method_with_state(stringify!($rpc_method), |$state:&$s, json_params| { 
+            #[derive(Debug)]
+            struct Params { $( $n:$t, ) + }            
+            impl $crate::serialize::Decodable for Params {
+                fn decode<D: $crate::serialize::Decoder>(d: &mut D) -> ::std::result::Result<Params, D::Error> {
+                    d.read_struct("Params", 0usize, |_d| -> _ {
+                        ::std::result::Result::Ok(Params{
+                            $(
+                                $n: match _d.read_struct_field(stringify!($n), 0usize, $crate::serialize::Decodable::decode) {
+                                    ::std::result::Result::Ok(v) => v,
+                                    ::std::result::Result::Err(v) => return ::std::result::Result::Err(v),
+                                },
+                            ) +
+                        }) 
+                    })
+                }
+            }           
+            let mut decoder = $crate::serialize::json::Decoder::new(json_params);
+            let rpc_params:Params = match $crate::serialize::Decodable::decode(&mut decoder) {
+                Ok(p) => p,
+                Err(_) => return Err(Error::predefined(-32602, None))
+            };
+            $( let $n:$t = rpc_params.$n; ) +                                    
+            
+            $rpc_block        
+        })        
+    };        
+    ( $rpc_struct:expr, $rpc_method:expr, &$state:ident : $s:ty, $n:ident[$t:ty], $rpc_block:block ) => {
+        $rpc_struct.register_method_with_state(stringify!($rpc_method), |$state:&$s, json_params| {                                     
+            let mut $n:Vec<$t> = Vec::new();
+            match json_params {
+                $crate::serialize::json::Json::Array(a) => {
+                    for v in a {
+                        let mut decoder = $crate::serialize::json::Decoder::new(v);
+                        let val:$t = match $crate::serialize::Decodable::decode(&mut decoder) {                        
+                            Ok(p) => p,
+                            Err(_) => return Err(Error::predefined(-32602, None))
+                        };
+                        $n.push(val);
+                    }
+                },
+                _ => return Err(Error::predefined(-32602, None))
+            }
+            $rpc_block        
+        })     
+    };                 
+    ( $rpc_struct:expr, $rpc_method:expr, &$state:ident : $s:ty, $n:ident, $rpc_block:block ) => {
+        $rpc_struct.register_method_with_state(stringify!($rpc_method), |$state:&$s, json_params| {                         
+            let $n:Json = json_params;
+            $rpc_block        
+        })     
+    };         
     ( $rpc_struct:expr, $rpc_method:expr, $($n:ident<$t:ty>);+ , $rpc_block:block ) => {
         $rpc_struct.register_method(stringify!($rpc_method), |json_params| { 
             #[derive(Debug)]
@@ -322,6 +696,11 @@ macro_rules! rpc_method {
 
 #[macro_export]
 macro_rules! rpc_method_no_params {
+    ( $rpc_struct:expr, $rpc_method:expr, &$state:ident : $s:ty, $rpc_block:block ) => {
+        $rpc_struct.register_method_with_state(stringify!($rpc_method), |$state:&$s, _| {                         
+            $rpc_block        
+        })     
+    };             
     ( $rpc_struct:expr, $rpc_method:expr, $rpc_block:block ) => {
         $rpc_struct.register_method(stringify!($rpc_method), |_| {                         
             $rpc_block        
@@ -361,14 +740,22 @@ macro_rules! rpc_params {
 
 #[cfg(test)]
 mod test {
-    use super::{Server,Error,Json};
+    use super::{Server,Error,Json,BatchExecution,Middleware,Request,Id,DEFAULT_BATCH_LIMIT};
+    use super::Context as RpcContext;
+    use asynchronous::Deferred;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
     use super::serialize::json::ToJson;
     use std::collections::BTreeMap;
-    use std::thread;    
+    use std::thread;
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
 
     #[test]
     fn test_method_by_name() {
-        let mut rpc_server = Server::new();
+        let rpc_server = Server::new();
         rpc_method!(rpc_server, Subtract, oper1<u64>;oper2<u64>, {                
             Ok(Json::U64(oper1 - oper2))        
         });
@@ -384,12 +771,12 @@ mod test {
             },
             None => unreachable!(),
         };
-        thread::sleep_ms(300);
+        thread::sleep(Duration::from_millis(300));
     }
 
     #[test]
     fn test_method_by_position() {
-        let mut rpc_server = Server::new();
+        let rpc_server = Server::new();
         rpc_method!(rpc_server, Multiply, values[u64], {        
             let mut r = 1;
             for v in values { r *= v }
@@ -407,12 +794,12 @@ mod test {
             },
             None => unreachable!(),
         };
-        thread::sleep_ms(300);
+        thread::sleep(Duration::from_millis(300));
     }    
 
     #[test]
     fn test_manual_register() {
-        let mut rpc_server = Server::new();
+        let rpc_server = Server::new();
         rpc_server.register_method("Add", |json_params| {   // json_params: String
             // It uses a macro for parse the String into a Struct. rpc_params : { oper1:u64, oper2:u64 }
             let rpc_params = rpc_params!(json_params, oper1<u64>;oper2<u64> );
@@ -428,17 +815,17 @@ mod test {
                 assert!(data.is_object());
                 let obj = data.as_object().unwrap();
                 assert_eq!(obj.get("jsonrpc").unwrap().as_string().unwrap(), "2.0");
-                assert_eq!(obj.get("id").unwrap().as_f64().unwrap(), -1.4788f64);
+                assert_eq!((obj.get("id").unwrap().as_f64().unwrap() * 10000f64).round(), -14788f64);
                 assert_eq!(obj.get("result").unwrap().as_u64().unwrap(), 27);
             },
             None => unreachable!(),
         };
-        thread::sleep_ms(300);
+        thread::sleep(Duration::from_millis(300));
     }
 
     #[test]
     fn test_method_returns_array() {
-        let mut rpc_server = Server::new();
+        let rpc_server = Server::new();
         rpc_method!(rpc_server, Sequence, start<u64>;step<f64>;iterations<u64>, {                
             let mut value = start as f64;
             let mut res = Vec::new();
@@ -465,7 +852,7 @@ mod test {
             },
             None => unreachable!(),
         };
-        thread::sleep_ms(300);
+        thread::sleep(Duration::from_millis(300));
     }
 
     #[test]
@@ -485,7 +872,7 @@ mod test {
             }
         }
 
-        let mut rpc_server = Server::new();
+        let rpc_server = Server::new();
         rpc_method_no_params!(rpc_server, GetInfo, {                            
             let info = Info { amount : 15, price: 2.33, description: "Apples".to_string() };
             Ok(info.to_json())        
@@ -505,13 +892,13 @@ mod test {
             },
             None => unreachable!(),
         };
-        thread::sleep_ms(300);
+        thread::sleep(Duration::from_millis(300));
     }
 
 
     #[test]
     fn test_custom_error() {
-        let mut rpc_server = Server::new();
+        let rpc_server = Server::new();
         rpc_method!(rpc_server, Division, oper1<f64>;oper2<f64>, {                
             if oper2 == 0f64 {
                 Err(Error::custom(784, "Division by zero", Some(Json::F64(oper1))))
@@ -535,12 +922,12 @@ mod test {
             },
             None => unreachable!(),
         };
-        thread::sleep_ms(300);
+        thread::sleep(Duration::from_millis(300));
     }
 
     #[test]
     fn test_error_method_not_found() {
-        let mut rpc_server = Server::new();
+        let rpc_server = Server::new();
         rpc_method!(rpc_server, Subtract, oper1<u64>;oper2<u64>, {                
             Ok(Json::U64(oper1 - oper2))        
         });
@@ -551,7 +938,7 @@ mod test {
                 assert!(data.is_object());
                 let obj = data.as_object().unwrap();
                 assert_eq!(obj.get("jsonrpc").unwrap().as_string().unwrap(), "2.0");
-                assert_eq!(obj.get("id").unwrap().as_null().unwrap(), ());
+                assert_eq!(obj.get("id").unwrap().as_u64().unwrap(), 1234);
                 assert!(obj.get("error").unwrap().is_object());
                 let err = obj.get("error").unwrap().as_object().unwrap();
                 assert_eq!(err.get("code").unwrap().as_i64().unwrap(), -32601);
@@ -559,6 +946,722 @@ mod test {
             },
             None => unreachable!(),
         };
-        thread::sleep_ms(300);
-    }    
-}
\ No newline at end of file
+        thread::sleep(Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_batch() {
+        let rpc_server = Server::new();
+        rpc_method!(rpc_server, Subtract, oper1<u64>;oper2<u64>, {
+            Ok(Json::U64(oper1 - oper2))
+        });
+        let str_request = "[{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":{\"oper1\":23, \"oper2\":4}, \"id\":1},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":{\"oper1\":5, \"oper2\":4}},
+                            {\"foo\":\"boo\"},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Add\", \"params\":{\"oper1\":23, \"oper2\":4}, \"id\":\"5\"},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":{\"oper1\":42, \"oper2\":23}, \"id\":3}]".to_string();
+        match rpc_server.request(str_request) {
+            Some(str_response) => {
+                let data = Json::from_str(&str_response).unwrap();
+                assert!(data.is_array());
+                let arr = data.as_array().unwrap();
+                assert_eq!(arr.len(), 4);
+                assert_eq!(arr[0].find("id").unwrap().as_u64().unwrap(), 1);
+                assert_eq!(arr[0].find("result").unwrap().as_u64().unwrap(), 19);
+                assert_eq!(arr[1].find_path(&["error", "code"]).unwrap().as_i64().unwrap(), -32600);
+                assert_eq!(arr[2].find("id").unwrap().as_string().unwrap(), "5");
+                assert_eq!(arr[2].find_path(&["error", "code"]).unwrap().as_i64().unwrap(), -32601);
+                assert_eq!(arr[3].find("id").unwrap().as_u64().unwrap(), 3);
+                assert_eq!(arr[3].find("result").unwrap().as_u64().unwrap(), 19);
+            },
+            None => unreachable!(),
+        };
+        thread::sleep(Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_batch_empty_and_notifications() {
+        let rpc_server = Server::new();
+        rpc_method!(rpc_server, Subtract, oper1<u64>;oper2<u64>, {
+            Ok(Json::U64(oper1 - oper2))
+        });
+        match rpc_server.request("[]".to_string()) {
+            Some(str_response) => {
+                let data = Json::from_str(&str_response).unwrap();
+                assert!(data.is_object());
+                assert!(data.find("id").unwrap().is_null());
+                assert_eq!(data.find_path(&["error", "code"]).unwrap().as_i64().unwrap(), -32600);
+            },
+            None => unreachable!(),
+        };
+        match rpc_server.request("[1]".to_string()) {
+            Some(str_response) => {
+                let data = Json::from_str(&str_response).unwrap();
+                let arr = data.as_array().unwrap();
+                assert_eq!(arr.len(), 1);
+                assert_eq!(arr[0].find_path(&["error", "code"]).unwrap().as_i64().unwrap(), -32600);
+            },
+            None => unreachable!(),
+        };
+        let str_request = "[{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":{\"oper1\":23, \"oper2\":4}},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":{\"oper1\":5, \"oper2\":4}}]".to_string();
+        assert!(rpc_server.request(str_request).is_none());
+        thread::sleep(Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_batch_async() {
+        let rpc_server = Server::new();
+        rpc_method!(rpc_server, Multiply, values[u64], {
+            let mut r = 1;
+            for v in values { r *= v }
+            Ok(Json::U64(r))
+        });
+        let (tx, rx) = mpsc::channel();
+        let str_request = "[{\"jsonrpc\":\"2.0\",\"method\":\"Multiply\", \"params\":[5, 6, 7], \"id\":1},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Multiply\", \"params\":[1, 2]},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Multiply\", \"params\":[2, 3], \"id\":2}]".to_string();
+        rpc_server.request_async(str_request, move |str_response| tx.send(str_response).unwrap());
+        let data = Json::from_str(&rx.recv().unwrap()).unwrap();
+        let arr = data.as_array().unwrap();
+        assert_eq!(arr.len(), 2);
+        assert_eq!(arr[0].find("id").unwrap().as_u64().unwrap(), 1);
+        assert_eq!(arr[0].find("result").unwrap().as_u64().unwrap(), 210);
+        assert_eq!(arr[1].find("id").unwrap().as_u64().unwrap(), 2);
+        assert_eq!(arr[1].find("result").unwrap().as_u64().unwrap(), 6);
+
+        let (tx, rx) = mpsc::channel();
+        let str_request = "[{\"jsonrpc\":\"2.0\",\"method\":\"Multiply\", \"params\":[1, 2]}]".to_string();
+        rpc_server.request_async(str_request, move |str_response| tx.send(str_response).unwrap());
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+    }
+
+    #[test]
+    fn test_batch_series() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut rpc_server = Server::new();
+        rpc_server.set_batch_execution(BatchExecution::Series);
+        let log_c = log.clone();
+        rpc_server.register_method("Wait", move |json_params| {
+            let ms = rpc_params!(json_params, ms<u64>).ms;
+            thread::sleep(Duration::from_millis(ms));
+            log_c.lock().unwrap().push(ms);
+            Ok(Json::U64(ms))
+        });
+        let str_request = "[{\"jsonrpc\":\"2.0\",\"method\":\"Wait\", \"params\":{\"ms\":200}, \"id\":1},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Wait\", \"params\":{\"ms\":100}},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Wait\", \"params\":{\"ms\":0}, \"id\":3}]".to_string();
+        match rpc_server.request(str_request) {
+            Some(str_response) => {
+                let data = Json::from_str(&str_response).unwrap();
+                let arr = data.as_array().unwrap();
+                assert_eq!(arr.len(), 2);
+                assert_eq!(arr[0].find("result").unwrap().as_u64().unwrap(), 200);
+                assert_eq!(arr[1].find("result").unwrap().as_u64().unwrap(), 0);
+            },
+            None => unreachable!(),
+        };
+        assert_eq!(*log.lock().unwrap(), vec![200, 100, 0]);
+    }
+
+    #[test]
+    fn test_batch_parallel() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut rpc_server = Server::new();
+        rpc_server.set_batch_execution(BatchExecution::Parallel);
+        let log_c = log.clone();
+        rpc_server.register_method("Wait", move |json_params| {
+            let ms = rpc_params!(json_params, ms<u64>).ms;
+            thread::sleep(Duration::from_millis(ms));
+            log_c.lock().unwrap().push(ms);
+            Ok(Json::U64(ms))
+        });
+        let (tx, rx) = mpsc::channel();
+        let str_request = "[{\"jsonrpc\":\"2.0\",\"method\":\"Wait\", \"params\":{\"ms\":400}, \"id\":1},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Wait\", \"params\":{\"ms\":200}, \"id\":2},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Wait\", \"params\":{\"ms\":0}, \"id\":3}]".to_string();
+        rpc_server.request_async(str_request, move |str_response| tx.send(str_response).unwrap());
+        let data = Json::from_str(&rx.recv().unwrap()).unwrap();
+        let arr = data.as_array().unwrap();
+        assert_eq!(arr.len(), 3);
+        for (i, ms) in [400u64, 200, 0].iter().enumerate() {
+            assert_eq!(arr[i].find("id").unwrap().as_u64().unwrap(), i as u64 + 1);
+            assert_eq!(arr[i].find("result").unwrap().as_u64().unwrap(), *ms);
+        }
+        assert_eq!(*log.lock().unwrap(), vec![0, 200, 400]);
+    }
+
+    #[test]
+    fn test_batch_parallel_limit() {
+        // The default execution is limited too
+        for &(batch_execution, limit) in [(Some(BatchExecution::ParallelLimit(2)), 2), (None, DEFAULT_BATCH_LIMIT)].iter() {
+            let running = Arc::new(AtomicUsize::new(0));
+            let max_running = Arc::new(AtomicUsize::new(0));
+            let mut rpc_server = Server::new();
+            if let Some(batch_execution) = batch_execution { rpc_server.set_batch_execution(batch_execution) }
+            let (running_c, max_running_c) = (running.clone(), max_running.clone());
+            rpc_server.register_method("Work", move |_| {
+                let now = running_c.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running_c.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(100));
+                running_c.fetch_sub(1, Ordering::SeqCst);
+                Ok(Json::Boolean(true))
+            });
+            let batch:Vec<String> = (0..8).map(|i| format!("{{\"jsonrpc\":\"2.0\",\"method\":\"Work\", \"id\":{}}}", i)).collect();
+            match rpc_server.request(format!("[{}]", batch.join(","))) {
+                Some(str_response) => {
+                    let data = Json::from_str(&str_response).unwrap();
+                    let arr = data.as_array().unwrap();
+                    assert_eq!(arr.len(), 8);
+                    for (i, r) in arr.iter().enumerate() {
+                        assert_eq!(r.find("id").unwrap().as_u64().unwrap(), i as u64);
+                    }
+                },
+                None => unreachable!(),
+            };
+            assert_eq!(max_running.load(Ordering::SeqCst), limit);
+        }
+    }
+
+    #[test]
+    fn test_error_with_id() {
+        let rpc_server = Server::new();
+        rpc_method!(rpc_server, Subtract, oper1<u64>;oper2<u64>, {
+            Ok(Json::U64(oper1 - oper2))
+        });
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":[23, 4], \"id\":\"ab\"}".to_string();
+        match rpc_server.request(str_request) {
+            Some(str_response) => {
+                let data = Json::from_str(&str_response).unwrap();
+                assert_eq!(data.find("id").unwrap().as_string().unwrap(), "ab");
+                assert_eq!(data.find_path(&["error", "code"]).unwrap().as_i64().unwrap(), -32602);
+            },
+            None => unreachable!(),
+        };
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":7, \"id\":8}".to_string();
+        match rpc_server.request(str_request) {
+            Some(str_response) => {
+                let data = Json::from_str(&str_response).unwrap();
+                assert_eq!(data.find("id").unwrap().as_u64().unwrap(), 8);
+                assert_eq!(data.find_path(&["error", "code"]).unwrap().as_i64().unwrap(), -32600);
+            },
+            None => unreachable!(),
+        };
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"id\":[8]}".to_string();
+        match rpc_server.request(str_request) {
+            Some(str_response) => {
+                let data = Json::from_str(&str_response).unwrap();
+                assert!(data.find("id").unwrap().is_null());
+                assert_eq!(data.find_path(&["error", "code"]).unwrap().as_i64().unwrap(), -32600);
+            },
+            None => unreachable!(),
+        };
+    }
+
+    #[test]
+    fn test_notification_errors() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let mut rpc_server = Server::new();
+        let errors_c = errors.clone();
+        rpc_server.set_notification_error_hook(move |method, e| {
+            errors_c.lock().unwrap().push((method.to_string(), e.code()));
+        });
+        rpc_method!(rpc_server, Subtract, oper1<u64>;oper2<u64>, {
+            Ok(Json::U64(oper1 - oper2))
+        });
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Add\", \"params\":{\"oper1\":23, \"oper2\":4}}".to_string();
+        assert!(rpc_server.request(str_request).is_none());
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":\"x\"}".to_string();
+        assert!(rpc_server.request(str_request).is_none());
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":[23, 4]}".to_string();
+        assert!(rpc_server.request(str_request).is_none());
+        let (tx, rx) = mpsc::channel();
+        let str_request = "[{\"jsonrpc\":\"2.0\",\"method\":\"Add\"},{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":[23, 4]}]".to_string();
+        rpc_server.request_async(str_request, move |str_response| tx.send(str_response).unwrap());
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+        // Not notifications without a valid "jsonrpc"
+        for str_request in ["{\"method\":\"Subtract\"}", "{\"jsonrpc\":\"1.0\",\"method\":\"Subtract\"}"].iter() {
+            assert_eq!(rpc_server.request(str_request.to_string()).unwrap(),
+                       "{\"error\":{\"code\":-32600,\"message\":\"Invalid Request\"},\"id\":null,\"jsonrpc\":\"2.0\"}");
+        }
+
+        let mut errors = errors.lock().unwrap().clone();
+        errors.sort();
+        assert_eq!(errors, vec![("Add".to_string(), -32601), ("Add".to_string(), -32601),
+                                ("Subtract".to_string(), -32602), ("Subtract".to_string(), -32602),
+                                ("Subtract".to_string(), -32600)]);
+
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":7}".to_string();
+        assert!(rpc_server.request(str_request).is_some());
+    }
+
+    #[test]
+    fn test_null_id() {
+        let mut rpc_server = Server::new();
+        rpc_method!(rpc_server, Subtract, oper1<u64>;oper2<u64>, {
+            Ok(Json::U64(oper1 - oper2))
+        });
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":{\"oper1\":23, \"oper2\":4}, \"id\":null}".to_string();
+        match rpc_server.request(str_request.clone()) {
+            Some(str_response) => {
+                let data = Json::from_str(&str_response).unwrap();
+                assert!(data.find("id").unwrap().is_null());
+                assert_eq!(data.find("result").unwrap().as_u64().unwrap(), 19);
+            },
+            None => unreachable!(),
+        };
+        let (tx, rx) = mpsc::channel();
+        rpc_server.request_async("{\"jsonrpc\":\"2.0\",\"method\":\"Add\", \"id\":null}".to_string(), move |str_response| tx.send(str_response).unwrap());
+        let data = Json::from_str(&rx.recv().unwrap()).unwrap();
+        assert!(data.find("id").unwrap().is_null());
+        assert_eq!(data.find_path(&["error", "code"]).unwrap().as_i64().unwrap(), -32601);
+
+        rpc_server.set_strict_ids(true);
+        for str_request in &[str_request, "{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":{\"oper1\":23, \"oper2\":4}, \"id\":2.5}".to_string()] {
+            match rpc_server.request(str_request.clone()) {
+                Some(str_response) => {
+                    let data = Json::from_str(&str_response).unwrap();
+                    assert!(data.find("id").unwrap().is_null());
+                    assert_eq!(data.find_path(&["error", "code"]).unwrap().as_i64().unwrap(), -32600);
+                },
+                None => unreachable!(),
+            };
+        }
+    }
+
+    #[test]
+    fn test_method_panics() {
+        let mut rpc_server = Server::new();
+        rpc_method!(rpc_server, Divide, oper1<u64>;oper2<u64>, {
+            if oper2 == 0 { panic!("Division by zero") }
+            Ok(Json::U64(oper1 / oper2))
+        });
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Divide\", \"params\":{\"oper1\":23, \"oper2\":0}, \"id\":1}".to_string();
+        match rpc_server.request(str_request.clone()) {
+            Some(str_response) => {
+                let data = Json::from_str(&str_response).unwrap();
+                assert_eq!(data.find("id").unwrap().as_u64().unwrap(), 1);
+                assert_eq!(data.find_path(&["error", "code"]).unwrap().as_i64().unwrap(), -32603);
+                assert!(data.find_path(&["error", "data"]).is_none());
+            },
+            None => unreachable!(),
+        };
+        rpc_server.set_panic_data(true);
+        let (tx, rx) = mpsc::channel();
+        rpc_server.request_async(str_request, move |str_response| tx.send(str_response).unwrap());
+        let data = Json::from_str(&rx.recv_timeout(Duration::from_millis(1000)).unwrap()).unwrap();
+        assert_eq!(data.find_path(&["error", "code"]).unwrap().as_i64().unwrap(), -32603);
+        assert_eq!(data.find_path(&["error", "data"]).unwrap().as_string().unwrap(), "Division by zero");
+    }
+
+    type PendingState = Arc<Mutex<(Option<Result<Json,Error>>, Option<Waker>)>>;
+
+    struct Pending {
+        state: PendingState,
+    }
+
+    impl Future for Pending {
+        type Output = Result<Json,Error>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<Json,Error>> {
+            let mut state = self.state.lock().unwrap();
+            match state.0.take() {
+                Some(res) => Poll::Ready(res),
+                None => {
+                    state.1 = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    fn complete(state:&PendingState, res:Result<Json,Error>) {
+        let waker = {
+            let mut state = state.lock().unwrap();
+            state.0 = Some(res);
+            state.1.take()
+        };
+        if let Some(waker) = waker { waker.wake() }
+    }
+
+    fn pending_server() -> (Server, Arc<Mutex<Vec<PendingState>>>) {
+        let pending = Arc::new(Mutex::new(Vec::new()));
+        let rpc_server = Server::new();
+        let pending_c = pending.clone();
+        rpc_server.register_future_method("Query", move |_| {
+            let state = Arc::new(Mutex::new((None, None)));
+            pending_c.lock().unwrap().push(state.clone());
+            Pending { state }
+        });
+        (rpc_server, pending)
+    }
+
+    #[test]
+    fn test_future_method() {
+        let (rpc_server, pending) = pending_server();
+        let (tx, rx) = mpsc::channel();
+        rpc_server.request_async("{\"jsonrpc\":\"2.0\",\"method\":\"Query\", \"id\":1}".to_string(), move |str_response| {
+            tx.send((thread::current().id(), str_response)).unwrap()
+        });
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        let state = pending.lock().unwrap()[0].clone();
+        complete(&state, Ok(Json::String("rows".to_string())));
+        let (thread_id, str_response) = rx.recv().unwrap();
+        assert_eq!(thread_id, thread::current().id());
+        assert_eq!(str_response, "{\"id\":1,\"jsonrpc\":\"2.0\",\"result\":\"rows\"}");
+    }
+
+    #[test]
+    fn test_future_method_batch() {
+        let (rpc_server, pending) = pending_server();
+        let (tx, rx) = mpsc::channel();
+        let str_request = "[{\"jsonrpc\":\"2.0\",\"method\":\"Query\", \"id\":1},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Query\", \"id\":2},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Query\", \"id\":3}]".to_string();
+        rpc_server.request_async(str_request, move |str_response| tx.send(str_response).unwrap());
+        let states = pending.lock().unwrap().clone();
+        assert_eq!(states.len(), 3);
+        for (i, state) in states.iter().enumerate().rev() {
+            assert!(rx.try_recv().is_err());
+            complete(state, Ok(Json::U64(i as u64 * 10)));
+        }
+        let data = Json::from_str(&rx.recv().unwrap()).unwrap();
+        let arr = data.as_array().unwrap();
+        for (i, r) in arr.iter().enumerate() {
+            assert_eq!(r.find("id").unwrap().as_u64().unwrap(), i as u64 + 1);
+            assert_eq!(r.find("result").unwrap().as_u64().unwrap(), i as u64 * 10);
+        }
+    }
+
+    #[test]
+    fn test_deferred_method() {
+        let rpc_server = Server::new();
+        rpc_server.register_deferred_method("Add", |json_params| {
+            Deferred::new(move || {
+                let rpc_params = rpc_params!(json_params, oper1<u64>;oper2<u64> );
+                Ok(Json::U64(rpc_params.oper1 + rpc_params.oper2))
+            })
+        });
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Add\", \"params\":{\"oper1\":23, \"oper2\":4}, \"id\":1}".to_string();
+        assert_eq!(rpc_server.request(str_request).unwrap(), "{\"id\":1,\"jsonrpc\":\"2.0\",\"result\":27}");
+    }
+
+    struct Log(Arc<Mutex<Vec<String>>>, &'static str);
+
+    impl Middleware for Log {
+        fn before(&self, request:&mut Request, _context:&mut RpcContext) -> Option<Result<Json,Error>> {
+            self.0.lock().unwrap().push(format!("{} before {}", self.1, request.method));
+            None
+        }
+        fn after(&self, request:&Request, _context:&RpcContext, result:&mut Result<Json,Error>) {
+            self.0.lock().unwrap().push(format!("{} after {} {}", self.1, request.method, result.is_ok()));
+        }
+    }
+
+    /// Denies the methods starting with "Admin", doubles the params and increments the results.
+    struct Rules;
+
+    impl Middleware for Rules {
+        fn before(&self, request:&mut Request, _context:&mut RpcContext) -> Option<Result<Json,Error>> {
+            if request.method.starts_with("Admin") { return Some(Err(Error::custom(403, "Forbidden", None))) }
+            if let Some(v) = request.params.as_array().and_then(|a| a[0].as_u64()) { request.params = Json::Array(vec![Json::U64(v * 2)]) }
+            None
+        }
+        fn after(&self, _request:&Request, _context:&RpcContext, result:&mut Result<Json,Error>) {
+            if let Ok(Json::U64(v)) = *result { *result = Ok(Json::U64(v + 1)) }
+        }
+    }
+
+    #[test]
+    fn test_middleware() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut rpc_server = Server::new();
+        rpc_server.register_method("Echo", |json_params| Ok(json_params.as_array().unwrap()[0].clone()));
+        rpc_server.register_method("AdminReset", |_| unreachable!());
+        rpc_server.add_middleware(Log(log.clone(), "log"));
+        rpc_server.add_middleware(Rules);
+
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Echo\", \"params\":[5], \"id\":1}".to_string();
+        assert_eq!(rpc_server.request(str_request).unwrap(), "{\"id\":1,\"jsonrpc\":\"2.0\",\"result\":11}");
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"AdminReset\", \"id\":2}".to_string();
+        let data = Json::from_str(&rpc_server.request(str_request).unwrap()).unwrap();
+        assert_eq!(data.find_path(&["error", "code"]).unwrap().as_i64().unwrap(), 403);
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Missing\", \"id\":3}".to_string();
+        let data = Json::from_str(&rpc_server.request(str_request).unwrap()).unwrap();
+        assert_eq!(data.find_path(&["error", "code"]).unwrap().as_i64().unwrap(), -32601);
+        assert_eq!(*log.lock().unwrap(), vec!["log before Echo", "log after Echo true", "log before AdminReset", "log after AdminReset false",
+                                              "log before Missing", "log after Missing false"]);
+    }
+
+    #[test]
+    fn test_middleware_async_batch() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut rpc_server = Server::new();
+        rpc_server.set_batch_execution(BatchExecution::Series);
+        rpc_server.register_method("Echo", |json_params| Ok(json_params.as_array().unwrap()[0].clone()));
+        rpc_server.add_middleware(Rules);
+        rpc_server.add_middleware(Log(log.clone(), "log"));
+        let (tx, rx) = mpsc::channel();
+        let str_request = "[{\"jsonrpc\":\"2.0\",\"method\":\"Echo\", \"params\":[1], \"id\":1},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"AdminReset\", \"id\":2},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Echo\", \"params\":[3]}]".to_string();
+        rpc_server.request_async(str_request, move |str_response| tx.send(str_response).unwrap());
+        let data = Json::from_str(&rx.recv().unwrap()).unwrap();
+        let arr = data.as_array().unwrap();
+        assert_eq!(arr.len(), 2);
+        assert_eq!(arr[0].find("result").unwrap().as_u64().unwrap(), 3);
+        assert_eq!(arr[1].find_path(&["error", "code"]).unwrap().as_i64().unwrap(), 403);
+        // The second middleware isn't called when the first one returns a result
+        assert_eq!(*log.lock().unwrap(), vec!["log before Echo", "log after Echo true", "log before Echo", "log after Echo true"]);
+    }
+
+    /// Panics before the method "PanicBefore" and after the method "PanicAfter".
+    struct Panics;
+
+    impl Middleware for Panics {
+        fn before(&self, request:&mut Request, _context:&mut RpcContext) -> Option<Result<Json,Error>> {
+            if request.method == "PanicBefore" { panic!("Panic before") }
+            None
+        }
+        fn after(&self, request:&Request, _context:&RpcContext, _result:&mut Result<Json,Error>) {
+            if request.method == "PanicAfter" { panic!("Panic after") }
+        }
+    }
+
+    #[test]
+    fn test_middleware_panics() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut rpc_server = Server::new();
+        rpc_server.set_panic_data(true);
+        rpc_server.register_method("PanicBefore", |_| Ok(Json::Null));
+        rpc_server.register_method("PanicAfter", |_| Ok(Json::Null));
+        rpc_server.add_middleware(Log(log.clone(), "log"));
+        rpc_server.add_middleware(Panics);
+        let error = |response:&Json, message:&str| {
+            assert_eq!(response.find_path(&["error", "code"]).unwrap().as_i64().unwrap(), -32603);
+            assert_eq!(response.find_path(&["error", "data"]).unwrap().as_string().unwrap(), message);
+        };
+
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"PanicBefore\", \"id\":1}".to_string();
+        error(&Json::from_str(&rpc_server.request(str_request).unwrap()).unwrap(), "Panic before");
+        let (tx, rx) = mpsc::channel();
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"PanicAfter\", \"id\":2}".to_string();
+        rpc_server.request_async(str_request, move |str_response| tx.send(str_response).unwrap());
+        error(&Json::from_str(&rx.recv().unwrap()).unwrap(), "Panic after");
+        // The middlewares around the one that panics still see the result
+        assert_eq!(*log.lock().unwrap(), vec!["log before PanicBefore", "log after PanicBefore false", "log before PanicAfter", "log after PanicAfter false"]);
+
+        for batch_execution in [BatchExecution::Series, BatchExecution::Parallel].iter() {
+            rpc_server.set_batch_execution(*batch_execution);
+            let str_request = "[{\"jsonrpc\":\"2.0\",\"method\":\"PanicBefore\", \"id\":1},
+                                {\"jsonrpc\":\"2.0\",\"method\":\"PanicAfter\", \"id\":2},
+                                {\"jsonrpc\":\"2.0\",\"method\":\"PanicBefore\"}]".to_string();
+            let data = Json::from_str(&rpc_server.request(str_request.clone()).unwrap()).unwrap();
+            assert_eq!(data.as_array().unwrap().len(), 2);
+            error(&data[0], "Panic before");
+            error(&data[1], "Panic after");
+            let (tx, rx) = mpsc::channel();
+            rpc_server.request_async(str_request, move |str_response| tx.send(str_response).unwrap());
+            assert_eq!(Json::from_str(&rx.recv().unwrap()).unwrap(), data);
+        }
+    }
+
+    struct User(String);
+
+    /// Adds the user of the token in the metadata.
+    struct Auth;
+
+    impl Middleware for Auth {
+        fn before(&self, _request:&mut Request, context:&mut RpcContext) -> Option<Result<Json,Error>> {
+            let user = context.metadata().get("token").map(|t| User(t.to_uppercase()));
+            match user {
+                Some(user) => { context.extensions_mut().insert(user); None },
+                None => Some(Err(Error::custom(401, "Unauthorized", None)))
+            }
+        }
+    }
+
+    #[test]
+    fn test_context() {
+        let mut rpc_server = Server::new();
+        rpc_server.register_method_with_context("Whoami", |context, _| {
+            let user = context.extensions().get::<User>().unwrap();
+            let id = context.id().to_response_id().unwrap_or(Json::Null);
+            Ok(Json::String(format!("{} {} {} {}", context.method(), id, user.0, context.metadata()["peer_addr"])))
+        });
+        rpc_server.register_future_method_with_context("Id", |context, _| ::std::future::ready(Ok(context.id().to_response_id().unwrap())));
+        rpc_server.register_deferred_method_with_context("DeferredWhoami", |context, _| {
+            Deferred::new(move || Ok(Json::String(format!("{} {}", context.method(), context.extensions().get::<User>().unwrap().0))))
+        });
+        rpc_server.register_method("Echo", Ok);
+        rpc_server.add_middleware(Auth);
+
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Whoami\", \"id\":1}".to_string();
+        let data = Json::from_str(&rpc_server.request(str_request.clone()).unwrap()).unwrap();
+        assert_eq!(data.find_path(&["error", "code"]).unwrap().as_i64().unwrap(), 401);
+
+        let mut context = RpcContext::new();
+        context.metadata_mut().insert("token".to_string(), "ann".to_string());
+        context.metadata_mut().insert("peer_addr".to_string(), "10.0.0.1:80".to_string());
+        assert_eq!(*context.id(), Id::Absent);
+        assert_eq!(rpc_server.request_with_context(str_request, &context).unwrap(), "{\"id\":1,\"jsonrpc\":\"2.0\",\"result\":\"Whoami 1 ANN 10.0.0.1:80\"}");
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"DeferredWhoami\", \"id\":2}".to_string();
+        assert_eq!(rpc_server.request_with_context(str_request, &context).unwrap(), "{\"id\":2,\"jsonrpc\":\"2.0\",\"result\":\"DeferredWhoami ANN\"}");
+
+        let (tx, rx) = mpsc::channel();
+        let str_request = "[{\"jsonrpc\":\"2.0\",\"method\":\"Id\", \"id\":\"a\"},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Echo\", \"params\":[2], \"id\":\"b\"},
+                            {\"jsonrpc\":\"2.0\",\"method\":\"Id\", \"id\":\"c\"}]".to_string();
+        rpc_server.request_async_with_context(str_request, &context, move |str_response| tx.send(str_response).unwrap());
+        let data = Json::from_str(&rx.recv().unwrap()).unwrap();
+        let results:Vec<Json> = data.as_array().unwrap().iter().map(|r| r.find("result").unwrap().clone()).collect();
+        assert_eq!(results, vec![Json::String("a".to_string()), Json::from_str("[2]").unwrap(), Json::String("c".to_string())]);
+    }
+
+    #[test]
+    fn test_state() {
+        struct Counter { hits: AtomicUsize, base: u64 }
+        fn hits(counter:&Counter, _:Json) -> Result<Json,Error> {
+            Ok(Json::U64(counter.hits.fetch_add(1, Ordering::SeqCst) as u64 + 1))
+        }
+        let rpc_server = Server::new();
+        rpc_server.register_method_with_state("Missing", |_:&String, _| Ok(Json::Null));
+        rpc_server.set_state(Counter { hits: AtomicUsize::new(0), base: 100 });
+        rpc_server.register_method_with_state("Hits", hits);
+        rpc_method!(rpc_server, Add, &counter:Counter, value<u64>, {
+            Ok(Json::U64(counter.base + value))
+        });
+        rpc_method!(rpc_server, Sum, &counter:Counter, values[u64], {
+            Ok(Json::U64(values.iter().fold(counter.base, |a, v| a + v)))
+        });
+        rpc_method_no_params!(rpc_server, Base, &counter:Counter, {
+            Ok(Json::U64(counter.base))
+        });
+        let call = |method:&str, params:&str| {
+            let str_request = format!("{{\"jsonrpc\":\"2.0\",\"method\":\"{}\",\"params\":{},\"id\":1}}", method, params);
+            Json::from_str(&rpc_server.request(str_request).unwrap()).unwrap()
+        };
+        assert_eq!(call("Hits", "[]").find("result").unwrap().as_u64().unwrap(), 1);
+        assert_eq!(call("Hits", "[]").find("result").unwrap().as_u64().unwrap(), 2);
+        assert_eq!(call("Add", "{\"value\":5}").find("result").unwrap().as_u64().unwrap(), 105);
+        assert_eq!(call("Sum", "[1,2,3]").find("result").unwrap().as_u64().unwrap(), 106);
+        assert_eq!(call("Base", "[]").find("result").unwrap().as_u64().unwrap(), 100);
+        assert_eq!(call("Add", "{}").find("error").unwrap().find("code").unwrap().as_i64().unwrap(), -32602);
+        assert_eq!(call("Missing", "[]").find("error").unwrap().find("code").unwrap().as_i64().unwrap(), -32603);
+
+        // The state is replaced on a shared server
+        let rpc_server = Arc::new(rpc_server);
+        rpc_server.set_state(Counter { hits: AtomicUsize::new(0), base: 200 });
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Base\",\"id\":1}".to_string();
+        let data = Json::from_str(&rpc_server.request(str_request).unwrap()).unwrap();
+        assert_eq!(data.find("result").unwrap().as_u64().unwrap(), 200);
+    }
+
+    #[test]
+    fn test_mount() {
+        let mut users = Server::new();
+        users.register_method("create", |_| Ok(Json::String("user created".to_string())));
+        let admin = Server::new();
+        admin.register_method("reset", |_| Ok(Json::String("reset".to_string())));
+        users.set_separator("/");
+        users.mount("admin", admin);
+        let billing = Server::new();
+        billing.register_method("create", |_| Ok(Json::String("invoice created".to_string())));
+        let mut rpc_server = Server::new();
+        rpc_server.register_method("ping", |_| Ok(Json::String("pong".to_string())));
+        rpc_server.mount("user", users);
+        rpc_server.mount("billing", Arc::new(billing));
+        assert_eq!(rpc_server.method_names(), vec!["billing.create", "ping", "user.admin/reset", "user.create"]);
+        let call = |method:&str| {
+            let str_request = format!("{{\"jsonrpc\":\"2.0\",\"method\":\"{}\",\"id\":1}}", method);
+            Json::from_str(&rpc_server.request(str_request).unwrap()).unwrap()
+        };
+        assert_eq!(call("ping").find("result").unwrap().as_string().unwrap(), "pong");
+        assert_eq!(call("user.create").find("result").unwrap().as_string().unwrap(), "user created");
+        assert_eq!(call("billing.create").find("result").unwrap().as_string().unwrap(), "invoice created");
+        assert_eq!(call("user.admin/reset").find("result").unwrap().as_string().unwrap(), "reset");
+        for method in ["user.delete", "user/create", "user.", "usercreate", "user.admin.reset", "create"].iter() {
+            assert_eq!(call(method).find("error").unwrap().find("code").unwrap().as_i64().unwrap(), -32601);
+        }
+    }
+
+    #[test]
+    fn test_runtime_methods() {
+        let (tx_started, rx_started) = mpsc::channel();
+        let (tx_go, rx_go) = mpsc::channel::<()>();
+        let rx_go = Mutex::new(rx_go);
+        let mut rpc_server = Server::new();
+        rpc_server.register_method("version", move |_| {
+            tx_started.send(()).unwrap();
+            rx_go.lock().unwrap().recv().unwrap();
+            Ok(Json::U64(1))
+        });
+        let child = Server::new();
+        child.register_method("list", |_| Ok(Json::Array(vec![])));
+        let child = Arc::new(child);
+        rpc_server.mount("user", child.clone());
+        let rpc_server = Arc::new(rpc_server);
+        let call = |method:&str| {
+            let str_request = format!("{{\"jsonrpc\":\"2.0\",\"method\":\"{}\",\"id\":1}}", method);
+            Json::from_str(&rpc_server.request(str_request).unwrap()).unwrap()
+        };
+
+        let (tx, rx) = mpsc::channel();
+        rpc_server.request_async("{\"jsonrpc\":\"2.0\",\"method\":\"version\",\"id\":1}".to_string(), move |r| tx.send(r).unwrap());
+        rx_started.recv().unwrap();
+        assert!(rpc_server.replace_method("version", |_| Ok(Json::U64(2))));
+        tx_go.send(()).unwrap();
+        assert_eq!(Json::from_str(&rx.recv().unwrap()).unwrap().find("result").unwrap().as_u64().unwrap(), 1);
+        assert_eq!(call("version").find("result").unwrap().as_u64().unwrap(), 2);
+
+        assert!(!rpc_server.replace_method("echo", Ok));
+        assert!(rpc_server.has_method("echo"));
+        assert!(rpc_server.has_method("user.list"));
+        assert!(child.unregister_method("list"));
+        assert!(!rpc_server.has_method("user.list"));
+        assert_eq!(call("user.list").find("error").unwrap().find("code").unwrap().as_i64().unwrap(), -32601);
+        assert!(rpc_server.unregister_method("version"));
+        assert!(!rpc_server.unregister_method("version"));
+        assert!(!rpc_server.has_method("version"));
+        assert_eq!(call("version").find("error").unwrap().find("code").unwrap().as_i64().unwrap(), -32601);
+        assert_eq!(rpc_server.method_names(), vec!["echo"]);
+
+        // The methods of any form can be replaced while the server is shared
+        rpc_server.register_method_with_context("echo", |context, _| Ok(Json::String(context.method().to_string())));
+        assert_eq!(call("echo").find("result").unwrap().as_string().unwrap(), "echo");
+        rpc_server.register_future_method("echo", |_| ::std::future::ready(Ok(Json::U64(3))));
+        assert_eq!(call("echo").find("result").unwrap().as_u64().unwrap(), 3);
+        rpc_server.register_deferred_method("echo", |_| Deferred::<Json,Error>::new(|| Ok(Json::U64(4))));
+        assert_eq!(call("echo").find("result").unwrap().as_u64().unwrap(), 4);
+        rpc_server.register_method_with_state("echo", |_:&String, _| Ok(Json::Null));
+        assert_eq!(call("echo").find("error").unwrap().find("code").unwrap().as_i64().unwrap(), -32603);
+        assert_eq!(rpc_server.method_names(), vec!["echo"]);
+    }
+
+    #[test]
+    fn test_large_invalid_batch() {
+        let str_request = format!("[{}]", vec!["1"; 10000].join(","));
+        for batch_execution in [BatchExecution::Series, BatchExecution::Parallel].iter() {
+            let mut rpc_server = Server::new();
+            rpc_server.set_batch_execution(*batch_execution);
+            let rpc_server = Arc::new(rpc_server);
+            let rpc_server_c = rpc_server.clone();
+            let str_request_c = str_request.clone();
+            // Transports run the connections in threads with the default stack of 2 MiB
+            let response = thread::Builder::new().stack_size(2 * 1024 * 1024).spawn(move || {
+                rpc_server_c.request(str_request_c).unwrap()
+            }).unwrap().join().unwrap();
+            let data = Json::from_str(&response).unwrap();
+            assert_eq!(data.as_array().unwrap().len(), 10000);
+            assert_eq!(data[0].find("error").unwrap().find("code").unwrap().as_i64().unwrap(), -32600);
+
+            let (tx, rx) = mpsc::channel();
+            let rpc_server_c = rpc_server.clone();
+            let str_request_c = str_request.clone();
+            thread::Builder::new().stack_size(2 * 1024 * 1024).spawn(move || {
+                rpc_server_c.request_async(str_request_c, move |r| tx.send(r).unwrap())
+            }).unwrap().join().unwrap();
+            assert_eq!(Json::from_str(&rx.recv().unwrap()).unwrap().as_array().unwrap().len(), 10000);
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use super::{Context, Error, Json, Request};
+
+/// Intercepts the calls of a **Server**: requests, notifications and the calls of batches.
+/// The middlewares are called in the order they were added, and in reverse order for the results.
+/// A middleware that panics gives "Internal error", like a method that panics.
+pub trait Middleware: Send + Sync {
+    /// Called before the method, also when it doesn't exist. The request can be changed, for instance its params,
+    /// and extensions can be added to the context for the method.
+    /// Returning **Some** skips the method and the following middlewares.
+    fn before(&self, _request:&mut Request, _context:&mut Context) -> Option<Result<Json,Error>> {
+        None
+    }
+
+    /// Called with the result of the method. Only the middlewares whose **before** returned **None** are called.
+    fn after(&self, _request:&Request, _context:&Context, _result:&mut Result<Json,Error>) {
+    }
+}
@@ -0,0 +1,147 @@
+use std::sync::{mpsc, Arc};
+use super::{Client, Context, Error, Json, Server};
+
+type Sender = Arc<dyn Fn(String) + Send + Sync>;
+
+/// Endpoint that is both a **Server** and a **Client** over the same connection, as in LSP.
+/// The messages received must be passed to **receive**: the requests and notifications
+/// are executed by the server, and the responses are matched with the pending calls.
+/// Everything to send, requests and responses, goes through **sender**.
+pub struct Peer {
+    server: Arc<Server>,
+    client: Client,
+    sender: Sender,
+    context: Context,
+}
+
+impl Peer {
+    pub fn new<F>(server:Arc<Server>, sender:F) -> Peer where F: Fn(String) + Send + Sync + 'static {
+        Peer::with_context(server, Context::new(), sender)
+    }
+
+    /// Like **new**, the methods of the server receive a copy of **context** with the id and the method of their request.
+    pub fn with_context<F>(server:Arc<Server>, context:Context, sender:F) -> Peer where F: Fn(String) + Send + Sync + 'static {
+        Peer {
+            server,
+            client: Client::new(),
+            sender: Arc::new(sender),
+            context,
+        }
+    }
+
+    /// Routes a message received: it goes to the server if it has **method**, and to the client otherwise.
+    /// The batches are split between both sides.
+    pub fn receive(&self, message:String) {
+        let data = match Json::from_str(&message) {
+            Ok(d) => d,
+            Err(_) => return self.serve(message)
+        };
+        match data {
+            Json::Array(ref batch) if !batch.is_empty() => {
+                let (requests, responses):(Vec<Json>, Vec<Json>) = batch.iter().cloned().partition(Peer::is_request);
+                if !requests.is_empty() { self.serve(Json::Array(requests).to_string()) }
+                if !responses.is_empty() { let _ = self.client.response(Json::Array(responses).to_string()); }
+            },
+            ref d if Peer::is_request(d) || d.as_object().is_none() => self.serve(message),
+            _ => { let _ = self.client.response(message); }
+        }
+    }
+
+    /// Sends a request. **f_response** is called with the result once the response is received.
+    pub fn request<F>(&self, method:&str, params:Json, f_response:F) where F: FnOnce(Result<Json,Error>) + Send + 'static {
+        (self.sender)(self.client.request(method, params, f_response))
+    }
+
+    /// Sends a request and waits for its result. It mustn't be called from the thread that calls **receive**.
+    pub fn call(&self, method:&str, params:Json) -> Result<Json,Error> {
+        let (tx, rx) = mpsc::channel();
+        self.request(method, params, move |res| { let _ = tx.send(res); });
+        match rx.recv() {
+            Ok(res) => res,
+            Err(_) => Err(Error::predefined(-32603, None))
+        }
+    }
+
+    pub fn notify(&self, method:&str, params:Json) {
+        (self.sender)(self.client.notification(method, params))
+    }
+
+    /// Number of calls waiting for a response.
+    pub fn pending(&self) -> usize {
+        self.client.pending()
+    }
+
+    /// Calls every pending callback with **error**. Transports use it when the connection is closed.
+    pub fn cancel_pending(&self, error:Error) {
+        self.client.cancel_pending(error)
+    }
+
+    fn serve(&self, message:String) {
+        let sender = self.sender.clone();
+        self.server.request_async_with_context(message, &self.context, move |str_response| sender(str_response));
+    }
+
+    fn is_request(data:&Json) -> bool {
+        data.as_object().is_some_and(|o| o.contains_key("method"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Peer;
+    use super::super::{Json, Server};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+
+    /// Connects two peers with channels. Each one receives its messages in its own thread.
+    fn connect(server_a:Server, server_b:Server) -> (Arc<Peer>, Arc<Peer>) {
+        let (tx_a, rx_a) = mpsc::channel::<String>();
+        let (tx_b, rx_b) = mpsc::channel::<String>();
+        let (tx_a, tx_b) = (Mutex::new(tx_a), Mutex::new(tx_b));
+        let peer_a = Arc::new(Peer::new(Arc::new(server_a), move |m| { let _ = tx_b.lock().unwrap().send(m); }));
+        let peer_b = Arc::new(Peer::new(Arc::new(server_b), move |m| { let _ = tx_a.lock().unwrap().send(m); }));
+        let (peer_a_c, peer_b_c) = (peer_a.clone(), peer_b.clone());
+        thread::spawn(move || for m in rx_a { peer_a_c.receive(m) });
+        thread::spawn(move || for m in rx_b { peer_b_c.receive(m) });
+        (peer_a, peer_b)
+    }
+
+    #[test]
+    fn test_both_sides() {
+        let names = Server::new();
+        names.register_method("Name", |_| Ok(Json::String("A".to_string())));
+        let greeter_slot:Arc<Mutex<Option<Arc<Peer>>>> = Arc::new(Mutex::new(None));
+        let slot = greeter_slot.clone();
+        let greetings = Server::new();
+        // Calls the other side while handling a request
+        greetings.register_method("Greet", move |_| {
+            let greeter = slot.lock().unwrap().clone().unwrap();
+            let name = greeter.call("Name", Json::Null)?;
+            Ok(Json::String(format!("Hello {}", name.as_string().unwrap())))
+        });
+        let (greeter, other) = connect(greetings, names);
+        *greeter_slot.lock().unwrap() = Some(greeter.clone());
+
+        assert_eq!(other.call("Greet", Json::Null), Ok(Json::String("Hello A".to_string())));
+        assert_eq!(greeter.call("Greet", Json::Null).unwrap_err().code(), -32601);
+        assert_eq!(greeter.pending(), 0);
+        assert_eq!(other.pending(), 0);
+    }
+
+    #[test]
+    fn test_mixed_batch() {
+        let server = Server::new();
+        server.register_method("Echo", Ok);
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let peer = Peer::new(Arc::new(server), move |m| { let _ = tx.lock().unwrap().send(m); });
+        let (tx_r, rx_r) = mpsc::channel();
+        peer.request("Remote", Json::Null, move |res| tx_r.send(res).unwrap());
+        let sent = Json::from_str(&rx.recv().unwrap()).unwrap();
+        assert_eq!(sent.find("id"), Some(&Json::U64(1)));
+
+        peer.receive("[{\"jsonrpc\":\"2.0\",\"result\":7,\"id\":1},{\"jsonrpc\":\"2.0\",\"method\":\"Echo\",\"params\":[3],\"id\":1}]".to_string());
+        assert_eq!(rx_r.recv().unwrap(), Ok(Json::U64(7)));
+        assert_eq!(Json::from_str(&rx.recv().unwrap()).unwrap(), Json::from_str("[{\"jsonrpc\":\"2.0\",\"result\":[3],\"id\":1}]").unwrap());
+    }
+}
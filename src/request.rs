@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use serialize::json::ToJson;
+use super::{Error, Json};
+
+/// The id of a request.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Id {
+    /// The request doesn't include an id: it's a notification.
+    Absent,
+    /// The request includes **"id": null**. It's answered with a null id.
+    Null,
+    /// A String or a Number.
+    Value(Json),
+}
+
+impl Id {
+    /// The id of the response. **None** for notifications.
+    pub fn to_response_id(&self) -> Option<Json> {
+        match *self {
+            Id::Absent => None,
+            Id::Null => Some(Json::Null),
+            Id::Value(ref v) => Some(v.clone()),
+        }
+    }
+}
+
+/// A validated JSON-RPC 2.0 request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    /// **Json::Array** or **Json::Object**. **Json::Null** if the request doesn't include params.
+    pub params: Json,
+    pub id: Id,
+}
+
+impl Request {
+    pub fn new(method:&str, params:Json, id:Id) -> Request {
+        Request {
+            method: method.to_string(), params, id
+        }
+    }
+
+    /// Validates the request object. Returns the error "Invalid Request" if it isn't a valid request.
+    pub fn parse(data:&Json) -> Result<Request, Error> {
+        Request::parse_with(data, false)
+    }
+
+    /// Same as **parse**, but null ids and numbers with fractional parts are not accepted as ids.
+    pub fn parse_strict(data:&Json) -> Result<Request, Error> {
+        Request::parse_with(data, true)
+    }
+
+    /// Reads only the id of the request object, so errors found later can be returned with the same id.
+    pub fn parse_id(data:&Json) -> Result<Id, Error> {
+        Request::parse_id_with(data, false)
+    }
+
+    /// Same as **parse_id**, but null ids and numbers with fractional parts are not accepted.
+    pub fn parse_id_strict(data:&Json) -> Result<Id, Error> {
+        Request::parse_id_with(data, true)
+    }
+
+    pub fn is_notification(&self) -> bool {
+        self.id == Id::Absent
+    }
+
+    fn parse_with(data:&Json, strict:bool) -> Result<Request, Error> {
+        let id = Request::parse_id_with(data, strict)?;
+        let obj = match data.as_object() {
+            Some(s) => s,
+            None => return Err(Error::predefined(-32600, None))
+        };
+        match obj.get("jsonrpc") {
+            Some(o) => match o.as_string() {
+                Some(s) => if s!="2.0" { return Err(Error::predefined(-32600, None)) },
+                None => return Err(Error::predefined(-32600, None))
+            },
+            None => return Err(Error::predefined(-32600, None))
+        };
+        let method = match obj.get("method") {
+            Some(o) => match o.as_string() {
+                Some(s) => s,
+                None => return Err(Error::predefined(-32600, None))
+            },
+            None => return Err(Error::predefined(-32600, None))
+        };
+        let params = match obj.get("params") {
+            Some(o) => match *o {
+                Json::Array(_) | Json::Object(_) => o.clone(),
+                _ => return Err(Error::predefined(-32600, None))
+            },
+            None => Json::Null
+        };
+        Ok(Request::new(method, params, id))
+    }
+
+    fn parse_id_with(data:&Json, strict:bool) -> Result<Id, Error> {
+        let obj = match data.as_object() {
+            Some(s) => s,
+            None => return Err(Error::predefined(-32600, None))
+        };
+        match obj.get("id") {
+            Some(o) => match *o {
+                Json::F64(v) if strict && v.fract() != 0f64 => Err(Error::predefined(-32600, None)),
+                Json::String(_) | Json::I64(_) | Json::U64(_) | Json::F64(_) => Ok(Id::Value(o.clone())),
+                Json::Null if !strict => Ok(Id::Null),
+                _ => Err(Error::predefined(-32600, None))
+            },
+            None => Ok(Id::Absent)
+        }
+    }
+}
+
+impl ToJson for Request {
+    fn to_json(&self) -> Json {
+        let mut req_object = BTreeMap::new();
+        req_object.insert("jsonrpc".to_string(), Json::String("2.0".to_string()));
+        req_object.insert("method".to_string(), Json::String(self.method.clone()));
+        if !self.params.is_null() {
+            req_object.insert("params".to_string(), self.params.clone());
+        }
+        if let Some(id) = self.id.to_response_id() {
+            req_object.insert("id".to_string(), id);
+        }
+        Json::Object(req_object)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Id, Request};
+    use super::super::Json;
+    use serialize::json::ToJson;
+
+    #[test]
+    fn test_parse() {
+        let data = Json::from_str("{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":[42, 23], \"id\":\"a1\"}").unwrap();
+        let request = Request::parse(&data).unwrap();
+        assert_eq!(request.method, "Subtract");
+        assert_eq!(request.params, Json::from_str("[42, 23]").unwrap());
+        assert_eq!(request.id, Id::Value(Json::String("a1".to_string())));
+        assert!(!request.is_notification());
+        assert_eq!(request.to_json(), data);
+
+        let data = Json::from_str("{\"jsonrpc\":\"2.0\",\"method\":\"Update\"}").unwrap();
+        let request = Request::parse(&data).unwrap();
+        assert_eq!(request.params, Json::Null);
+        assert!(request.is_notification());
+        assert_eq!(request.to_json(), data);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for str_request in &["[]", "{\"method\":\"Update\"}", "{\"jsonrpc\":\"1.0\",\"method\":\"Update\"}",
+                             "{\"jsonrpc\":\"2.0\",\"method\":1}", "{\"jsonrpc\":\"2.0\",\"method\":\"Update\",\"params\":\"x\"}",
+                             "{\"jsonrpc\":\"2.0\",\"method\":\"Update\",\"id\":[1]}"] {
+            let data = Json::from_str(str_request).unwrap();
+            assert_eq!(Request::parse(&data).unwrap_err().code(), -32600);
+        }
+    }
+
+    #[test]
+    fn test_parse_id() {
+        let data = Json::from_str("{\"jsonrpc\":\"2.0\",\"method\":1,\"id\":7}").unwrap();
+        assert!(Request::parse(&data).is_err());
+        assert_eq!(Request::parse_id(&data).unwrap(), Id::Value(Json::U64(7)));
+        let data = Json::from_str("{\"jsonrpc\":\"2.0\",\"method\":\"Update\",\"id\":{}}").unwrap();
+        assert_eq!(Request::parse_id(&data).unwrap_err().code(), -32600);
+    }
+
+    #[test]
+    fn test_parse_null_id() {
+        let data = Json::from_str("{\"jsonrpc\":\"2.0\",\"method\":\"Update\",\"id\":null}").unwrap();
+        let request = Request::parse(&data).unwrap();
+        assert_eq!(request.id, Id::Null);
+        assert!(!request.is_notification());
+        assert_eq!(request.to_json(), data);
+        assert_eq!(Request::parse_strict(&data).unwrap_err().code(), -32600);
+
+        let data = Json::from_str("{\"jsonrpc\":\"2.0\",\"method\":\"Update\",\"id\":1.5}").unwrap();
+        assert_eq!(Request::parse(&data).unwrap().id, Id::Value(Json::F64(1.5)));
+        assert_eq!(Request::parse_strict(&data).unwrap_err().code(), -32600);
+        let data = Json::from_str("{\"jsonrpc\":\"2.0\",\"method\":\"Update\",\"id\":-3}").unwrap();
+        assert_eq!(Request::parse_strict(&data).unwrap().id, Id::Value(Json::I64(-3)));
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use serialize::json::ToJson;
+use super::{Error, Json};
+
+/// A JSON-RPC 2.0 response with the result or the error of a request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    /// **Json::Null** if the id of the request couldn't be determined.
+    pub id: Json,
+    pub result: Result<Json, Error>,
+}
+
+impl Response {
+    pub fn new(id:Json, result:Result<Json,Error>) -> Response {
+        Response {
+            id, result
+        }
+    }
+
+    /// Validates the response object. Returns the error "Invalid Request" if it isn't a valid response.
+    pub fn parse(data:&Json) -> Result<Response, Error> {
+        let obj = match data.as_object() {
+            Some(s) => s,
+            None => return Err(Error::predefined(-32600, Some(data.clone())))
+        };
+        match obj.get("jsonrpc").and_then(|v| v.as_string()) {
+            Some("2.0") => (),
+            _ => return Err(Error::predefined(-32600, Some(data.clone())))
+        };
+        let result = match (obj.get("result"), obj.get("error")) {
+            (Some(r), None) => Ok(r.clone()),
+            (None, Some(e)) => match Error::from_object(e) {
+                Some(e) => Err(e),
+                None => return Err(Error::predefined(-32600, Some(data.clone())))
+            },
+            _ => return Err(Error::predefined(-32600, Some(data.clone())))
+        };
+        let id = match obj.get("id") {
+            Some(o) => o.clone(),
+            None => return Err(Error::predefined(-32600, Some(data.clone())))
+        };
+        Ok(Response::new(id, result))
+    }
+}
+
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        let mut resp_object = BTreeMap::new();
+        resp_object.insert("jsonrpc".to_string(), Json::String("2.0".to_string()));
+        resp_object.insert("id".to_string(), self.id.clone());
+        match self.result {
+            Ok(ref v) => { resp_object.insert("result".to_string(), v.clone()); } ,
+            Err(ref e) => { resp_object.insert("error".to_string(), e.as_object()); }
+        }
+        Json::Object(resp_object)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Response;
+    use super::super::{Error, Json};
+    use serialize::json::ToJson;
+
+    #[test]
+    fn test_serialize() {
+        let response = Response::new(Json::U64(1), Ok(Json::U64(19)));
+        assert_eq!(response.to_json().to_string(), "{\"id\":1,\"jsonrpc\":\"2.0\",\"result\":19}");
+        let response = Response::new(Json::Null, Err(Error::predefined(-32700, None)));
+        assert_eq!(response.to_json().to_string(), "{\"error\":{\"code\":-32700,\"message\":\"Parse error\"},\"id\":null,\"jsonrpc\":\"2.0\"}");
+        assert_eq!(Response::parse(&response.to_json()).unwrap(), response);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for str_response in &["1", "{\"id\":1,\"result\":1}", "{\"jsonrpc\":\"2.0\",\"result\":1}",
+                              "{\"id\":1,\"jsonrpc\":\"2.0\",\"result\":1,\"error\":{\"code\":1,\"message\":\"\"}}",
+                              "{\"id\":1,\"jsonrpc\":\"2.0\",\"error\":{\"code\":1}}"] {
+            let data = Json::from_str(str_response).unwrap();
+            assert_eq!(Response::parse(&data).unwrap_err().code(), -32600);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::any::Any;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+use super::{Error, Json};
+
+pub type BoxFuture = Pin<Box<dyn Future<Output=Result<Json,Error>> + Send>>;
+pub type Completion<T> = Box<dyn FnOnce(T) + Send>;
+
+/// Drives a future without any thread of its own: it's polled by the caller
+/// and then by whichever thread wakes it, until it's ready and **done** is called.
+struct Task {
+    future: Mutex<Option<BoxFuture>>,
+    done: Mutex<Option<Completion<Result<Json,Error>>>>,
+    notified: AtomicBool,
+    panic_data: bool,
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        Task::run(&self)
+    }
+}
+
+impl Task {
+    fn run(task:&Arc<Task>) {
+        task.notified.store(true, Ordering::SeqCst);
+        loop {
+            let mut future = match task.future.try_lock() {
+                Ok(f) => f,
+                Err(_) => return   // Another thread is polling and it will see the notification
+            };
+            while task.notified.swap(false, Ordering::SeqCst) {
+                let res = match *future {
+                    Some(ref mut f) => {
+                        let waker = Waker::from(task.clone());
+                        let mut cx = Context::from_waker(&waker);
+                        match panic::catch_unwind(AssertUnwindSafe(|| f.as_mut().poll(&mut cx))) {
+                            Ok(Poll::Pending) => continue,
+                            Ok(Poll::Ready(res)) => res,
+                            Err(payload) => Err(panic_error(payload, task.panic_data))
+                        }
+                    },
+                    None => return
+                };
+                *future = None;
+                drop(future);
+                if let Some(done) = task.done.lock().unwrap().take() { done(res) }
+                return
+            }
+            drop(future);
+            if !task.notified.load(Ordering::SeqCst) { return }
+        }
+    }
+}
+
+pub fn drive(future:BoxFuture, panic_data:bool, done:Completion<Result<Json,Error>>) {
+    let task = Arc::new(Task {
+        future: Mutex::new(Some(future)),
+        done: Mutex::new(Some(done)),
+        notified: AtomicBool::new(false),
+        panic_data,
+    });
+    Task::run(&task);
+}
+
+pub fn panic_error(payload:Box<dyn Any + Send>, panic_data:bool) -> Error {
+    let data = if panic_data {
+        match payload.downcast_ref::<&str>() {
+            Some(s) => Some(Json::String(s.to_string())),
+            None => payload.downcast_ref::<String>().map(|s| Json::String(s.clone()))
+        }
+    } else { None };
+    Error::predefined(-32603, data)
+}
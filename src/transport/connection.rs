@@ -0,0 +1,183 @@
+//! Generic connection to run a **Server**, or a **Peer**, over any channel that carries whole messages.
+//! A transport only needs to implement **Connection** and **Sink**.
+
+use std::io;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use super::super::{Context, Error, Json, Peer, Server};
+
+/// Outgoing side of a connection. It's cloned for every call in flight, so it can be used from many threads.
+pub trait Sink: Clone + Send + Sync + 'static {
+    /// Sends a whole message. The messages sent from different threads mustn't be mixed.
+    fn send(&self, message:String) -> io::Result<()>;
+}
+
+/// Incoming side of a connection.
+pub trait Connection {
+    type Sink: Sink;
+
+    /// Blocks until the next message is received. Returns **None** once the connection is closed.
+    fn receive(&mut self) -> Option<String>;
+
+    fn sink(&self) -> Self::Sink;
+
+    /// Context given to the methods of the requests received, with the metadata of the connection.
+    fn context(&self) -> Context {
+        Context::new()
+    }
+
+    /// Called by the drivers once the connection is closed and the calls in flight have finished.
+    fn closed(&mut self) {}
+}
+
+/// Executes every message received with **rpc_server** until the connection is closed.
+/// The responses are sent as soon as they're ready, so many calls can be in flight.
+/// Returns once the calls in flight have finished.
+pub fn serve<C>(connection:&mut C, rpc_server:&Server) where C: Connection {
+    let in_flight = Arc::new((Mutex::new(0), Condvar::new()));
+    let context = connection.context();
+    while let Some(message) = connection.receive() {
+        let sink = connection.sink();
+        let guard = InFlight::new(in_flight.clone());
+        rpc_server.request_async_with_context(message, &context, move |str_response| {
+            let _ = sink.send(str_response);
+            drop(guard);
+        });
+    }
+    let (ref count, ref condvar) = *in_flight;
+    let mut count = count.lock().unwrap();
+    while *count > 0 { count = condvar.wait(count).unwrap() }
+    connection.closed();
+}
+
+/// Returns a **Peer** over the connection. The messages are received in a new thread,
+/// and when the connection is closed the calls still pending fail.
+pub fn peer<C>(mut connection:C, rpc_server:Arc<Server>) -> Arc<Peer> where C: Connection + Send + 'static {
+    let sink = connection.sink();
+    let peer = Arc::new(Peer::with_context(rpc_server, connection.context(), move |message| { let _ = sink.send(message); }));
+    let peer_c = peer.clone();
+    thread::spawn(move || {
+        while let Some(message) = connection.receive() { peer_c.receive(message) }
+        peer_c.cancel_pending(Error::predefined(-32603, Some(Json::String("Connection closed".to_string()))));
+        connection.closed();
+    });
+    peer
+}
+
+/// Counts a call in flight until it's dropped, also when there's no response.
+struct InFlight(Arc<(Mutex<usize>, Condvar)>);
+
+impl InFlight {
+    fn new(in_flight:Arc<(Mutex<usize>, Condvar)>) -> InFlight {
+        *in_flight.0.lock().unwrap() += 1;
+        InFlight(in_flight)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let (ref count, ref condvar) = *self.0;
+        *count.lock().unwrap() -= 1;
+        condvar.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{peer, serve, Connection, Sink};
+    use super::super::super::{Context, Json, Server};
+    use std::io;
+    use std::sync::{mpsc, Arc, Mutex};
+
+    /// Connection over channels, as an adapter of a message bus would be.
+    struct Channel {
+        incoming: mpsc::Receiver<String>,
+        outgoing: ChannelSink,
+        closed: Arc<Mutex<bool>>,
+    }
+
+    #[derive(Clone)]
+    struct ChannelSink(Arc<Mutex<mpsc::Sender<String>>>);
+
+    impl Sink for ChannelSink {
+        fn send(&self, message:String) -> io::Result<()> {
+            self.0.lock().unwrap().send(message).map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+    }
+
+    impl Connection for Channel {
+        type Sink = ChannelSink;
+
+        fn receive(&mut self) -> Option<String> {
+            self.incoming.recv().ok()
+        }
+
+        fn sink(&self) -> ChannelSink {
+            self.outgoing.clone()
+        }
+
+        fn context(&self) -> Context {
+            let mut context = Context::new();
+            context.metadata_mut().insert("transport".to_string(), "channel".to_string());
+            context
+        }
+
+        fn closed(&mut self) {
+            *self.closed.lock().unwrap() = true;
+        }
+    }
+
+    /// Returns both ends of a connection.
+    fn pair() -> (Channel, Channel) {
+        let (tx_a, rx_a) = mpsc::channel();
+        let (tx_b, rx_b) = mpsc::channel();
+        let a = Channel { incoming: rx_a, outgoing: ChannelSink(Arc::new(Mutex::new(tx_b))), closed: Arc::new(Mutex::new(false)) };
+        let b = Channel { incoming: rx_b, outgoing: ChannelSink(Arc::new(Mutex::new(tx_a))), closed: Arc::new(Mutex::new(false)) };
+        (a, b)
+    }
+
+    #[test]
+    fn test_serve() {
+        let rpc_server = Server::new();
+        rpc_server.register_method("Echo", Ok);
+        let (mut server_side, client_side) = pair();
+        client_side.sink().send("{\"jsonrpc\":\"2.0\",\"method\":\"Echo\",\"params\":[1],\"id\":1}".to_string()).unwrap();
+        client_side.sink().send("{\"jsonrpc\":\"2.0\",\"method\":\"Echo\",\"params\":[2]}".to_string()).unwrap();
+        let Channel { incoming, outgoing, .. } = client_side;
+        drop(outgoing);
+
+        let closed = server_side.closed.clone();
+        serve(&mut server_side, &rpc_server);
+        assert!(*closed.lock().unwrap());
+        drop(server_side);
+        let responses:Vec<String> = incoming.iter().collect();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(Json::from_str(&responses[0]).unwrap(), Json::from_str("{\"jsonrpc\":\"2.0\",\"result\":[1],\"id\":1}").unwrap());
+    }
+
+    #[test]
+    fn test_peer() {
+        let rpc_server = Server::new();
+        rpc_server.register_method("Echo", Ok);
+        rpc_server.register_method_with_context("Transport", |context, _| {
+            Ok(context.metadata().get("transport").map_or(Json::Null, |t| Json::String(t.clone())))
+        });
+        let rpc_server = Arc::new(rpc_server);
+        let (a, b) = pair();
+        let closed = b.closed.clone();
+        let peer_a = peer(a, rpc_server.clone());
+        let peer_b = peer(b, rpc_server.clone());
+        assert_eq!(peer_a.call("Echo", Json::from_str("[1]").unwrap()), Ok(Json::from_str("[1]").unwrap()));
+        assert_eq!(peer_b.call("Echo", Json::from_str("[2]").unwrap()), Ok(Json::from_str("[2]").unwrap()));
+        assert_eq!(peer_a.call("Transport", Json::Null), Ok(Json::String("channel".to_string())));
+        assert!(!*closed.lock().unwrap());
+
+        // The calls pending fail when the other side is gone
+        let (a, b) = pair();
+        let peer_a = peer(a, rpc_server);
+        let (tx, rx) = mpsc::channel();
+        peer_a.request("Echo", Json::Null, move |res| tx.send(res).unwrap());
+        drop(b);
+        assert_eq!(rx.recv().unwrap().unwrap_err().code(), -32603);
+    }
+}
@@ -0,0 +1,393 @@
+//! Framing of messages in byte streams: one message per line,
+//! or **Content-Length** headers before each message as in LSP and DAP.
+
+use std::cmp;
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::sync::{Arc, Mutex};
+use super::connection::{self, Connection, Sink};
+use super::super::{Context, Error, Json, Response, Server};
+use serialize::json::ToJson;
+
+/// Default maximum size of a message: 16 MiB.
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+
+/// How messages are delimited in a byte stream.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Framing {
+    /// One message per line.
+    Newline,
+    /// **Content-Length: N\r\n\r\n** followed by N bytes. The **Content-Type** header is optional.
+    ContentLength,
+}
+
+#[derive(Debug)]
+pub enum FrameError {
+    /// The message is bigger than the maximum size. It's discarded and the next message can be read.
+    TooLarge(usize),
+    /// The headers aren't valid. The stream can't be read anymore.
+    InvalidHeader(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FrameError::TooLarge(size) => write!(f, "Message too large: {} bytes", size),
+            FrameError::InvalidHeader(ref header) => write!(f, "Invalid header: {}", header),
+            FrameError::Io(ref e) => e.fmt(f),
+        }
+    }
+}
+
+impl From<io::Error> for FrameError {
+    fn from(e:io::Error) -> FrameError {
+        FrameError::Io(e)
+    }
+}
+
+/// Reads framed messages from any **BufRead**. Partial reads and many messages in a single read are supported.
+pub struct MessageReader<R> {
+    reader: R,
+    framing: Framing,
+    max_size: usize,
+}
+
+impl<R> MessageReader<R> where R: BufRead {
+    pub fn new(reader:R, framing:Framing) -> MessageReader<R> {
+        MessageReader {
+            reader, framing,
+            max_size: MAX_MESSAGE_SIZE,
+        }
+    }
+
+    pub fn set_max_size(&mut self, max_size:usize) {
+        self.max_size = max_size;
+    }
+
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Returns the next message, or **None** at the end of the stream.
+    pub fn read_message(&mut self) -> Result<Option<String>, FrameError> {
+        match self.framing {
+            Framing::Newline => loop {
+                let (line, size) = match read_until(&mut self.reader, b'\n', self.max_size)? {
+                    Some(l) => l,
+                    None => return Ok(None)
+                };
+                if size > self.max_size { return Err(FrameError::TooLarge(size)) }
+                let line = String::from_utf8_lossy(&line).trim().to_string();
+                if !line.is_empty() { return Ok(Some(line)) }
+            },
+            Framing::ContentLength => {
+                let mut content_length = None;
+                loop {
+                    let (line, size) = match read_until(&mut self.reader, b'\n', MAX_HEADER_SIZE)? {
+                        Some(l) => l,
+                        None if content_length.is_none() => return Ok(None),
+                        None => return Err(FrameError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "End of stream in headers")))
+                    };
+                    if size > MAX_HEADER_SIZE { return Err(FrameError::InvalidHeader("Header too large".to_string())) }
+                    let line = String::from_utf8_lossy(&line).trim().to_string();
+                    if line.is_empty() {
+                        // Allows empty lines between messages
+                        if content_length.is_some() { break } else { continue }
+                    }
+                    let mut parts = line.splitn(2, ':');
+                    let name = parts.next().unwrap_or("").trim();
+                    let value = match parts.next() {
+                        Some(v) => v.trim(),
+                        None => return Err(FrameError::InvalidHeader(line.clone()))
+                    };
+                    if name.eq_ignore_ascii_case("Content-Length") {
+                        match value.parse::<usize>() {
+                            Ok(n) => content_length = Some(n),
+                            Err(_) => return Err(FrameError::InvalidHeader(line.clone()))
+                        }
+                    } else if !name.eq_ignore_ascii_case("Content-Type") {
+                        return Err(FrameError::InvalidHeader(line.clone()))
+                    }
+                }
+                let size = content_length.unwrap_or(0);
+                if size > self.max_size {
+                    io::copy(&mut (&mut self.reader).take(size as u64), &mut io::sink())?;
+                    return Err(FrameError::TooLarge(size))
+                }
+                let mut body = vec![0u8; size];
+                self.reader.read_exact(&mut body)?;
+                Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+            }
+        }
+    }
+}
+
+/// Writes the message with its framing in a single **write_all**, so writers sharing a lock don't mix their bytes.
+pub fn write_message<W>(writer:&mut W, framing:Framing, message:&str) -> io::Result<()> where W: Write {
+    let mut buffer = Vec::with_capacity(message.len() + 32);
+    match framing {
+        Framing::Newline => {
+            buffer.extend_from_slice(message.as_bytes());
+            buffer.push(b'\n');
+        },
+        Framing::ContentLength => {
+            buffer.extend_from_slice(format!("Content-Length: {}\r\n\r\n", message.len()).as_bytes());
+            buffer.extend_from_slice(message.as_bytes());
+        }
+    }
+    writer.write_all(&buffer)?;
+    writer.flush()
+}
+
+/// Connection over a byte stream with framed messages.
+/// Messages too large are answered with an "Invalid Request" error.
+pub struct StreamConnection<R, W> {
+    reader: MessageReader<R>,
+    sink: StreamSink<W>,
+    error: Option<FrameError>,
+    context: Context,
+}
+
+impl<R, W> StreamConnection<R, W> where R: BufRead, W: Write + Send + 'static {
+    /// The messages are written with the framing of **reader**.
+    pub fn new(reader:MessageReader<R>, writer:W) -> StreamConnection<R, W> {
+        let framing = reader.framing();
+        StreamConnection {
+            reader,
+            sink: StreamSink { writer: Arc::new(Mutex::new(writer)), framing },
+            error: None,
+            context: Context::new(),
+        }
+    }
+
+    /// Context with the metadata of the connection, for the methods.
+    pub fn set_context(&mut self, context:Context) {
+        self.context = context;
+    }
+
+    /// Returns the error that closed the connection, if it wasn't the end of the stream.
+    pub fn take_error(&mut self) -> Option<FrameError> {
+        self.error.take()
+    }
+}
+
+impl<R, W> Connection for StreamConnection<R, W> where R: BufRead, W: Write + Send + 'static {
+    type Sink = StreamSink<W>;
+
+    fn receive(&mut self) -> Option<String> {
+        loop {
+            match self.reader.read_message() {
+                Ok(message) => return message,
+                Err(FrameError::TooLarge(size)) => {
+                    let error = Error::predefined(-32600, Some(Json::String(format!("Message too large: {} bytes", size))));
+                    if let Err(e) = self.sink.send(Response::new(Json::Null, Err(error)).to_json().to_string()) {
+                        self.error = Some(FrameError::Io(e));
+                        return None
+                    }
+                },
+                Err(e) => {
+                    self.error = Some(e);
+                    return None
+                }
+            }
+        }
+    }
+
+    fn sink(&self) -> StreamSink<W> {
+        self.sink.clone()
+    }
+
+    fn context(&self) -> Context {
+        self.context.clone()
+    }
+}
+
+/// Writes whole messages to a byte stream shared by many threads.
+pub struct StreamSink<W> {
+    writer: Arc<Mutex<W>>,
+    framing: Framing,
+}
+
+impl<W> Clone for StreamSink<W> {
+    fn clone(&self) -> StreamSink<W> {
+        StreamSink { writer: self.writer.clone(), framing: self.framing }
+    }
+}
+
+impl<W> Sink for StreamSink<W> where W: Write + Send + 'static {
+    fn send(&self, message:String) -> io::Result<()> {
+        write_message(&mut *self.writer.lock().unwrap(), self.framing, &message)
+    }
+}
+
+/// Executes every message read with **rpc_server** until the end of the stream or an invalid header.
+/// The responses are written with the same framing as soon as they're ready, so many calls can be in flight.
+/// Messages too large are answered with an "Invalid Request" error.
+/// Returns once the calls in flight have finished.
+pub fn serve<R, W>(reader:MessageReader<R>, writer:W, rpc_server:&Server) -> Result<(), FrameError> where R: BufRead, W: Write + Send + 'static {
+    serve_with_context(reader, writer, Context::new(), rpc_server)
+}
+
+/// Like **serve**, the methods receive **context** with the metadata of the connection.
+pub fn serve_with_context<R, W>(reader:MessageReader<R>, writer:W, context:Context, rpc_server:&Server) -> Result<(), FrameError> where R: BufRead, W: Write + Send + 'static {
+    let mut connection = StreamConnection::new(reader, writer);
+    connection.set_context(context);
+    connection::serve(&mut connection, rpc_server);
+    match connection.take_error() {
+        Some(e) => Err(e),
+        None => Ok(())
+    }
+}
+
+/// Reads until **delim** keeping at most **max** bytes. Returns the bytes kept and the total size,
+/// or **None** at the end of the stream.
+fn read_until<R>(reader:&mut R, delim:u8, max:usize) -> io::Result<Option<(Vec<u8>, usize)>> where R: BufRead {
+    let mut buffer = Vec::new();
+    let mut size = 0;
+    loop {
+        let (found, used) = {
+            let available = match reader.fill_buf() {
+                Ok(b) => b,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e)
+            };
+            if available.is_empty() {
+                return Ok(if size == 0 { None } else { Some((buffer, size)) })
+            }
+            let (found, used) = match available.iter().position(|b| *b == delim) {
+                Some(i) => (true, i + 1),
+                None => (false, available.len())
+            };
+            let keep = cmp::min(used, max.saturating_sub(buffer.len()));
+            buffer.extend_from_slice(&available[..keep]);
+            (found, used)
+        };
+        reader.consume(used);
+        size += used;
+        if found { return Ok(Some((buffer, size - 1))) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{serve, write_message, FrameError, Framing, MessageReader};
+    use super::super::super::{Json, Server};
+    use std::io::{self, BufReader, Read, Write};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    /// Returns the data one byte at a time.
+    struct Trickle {
+        data: Vec<u8>,
+        position: usize,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buf:&mut [u8]) -> io::Result<usize> {
+            if self.position >= self.data.len() || buf.is_empty() { return Ok(0) }
+            buf[0] = self.data[self.position];
+            self.position += 1;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_content_length() {
+        let mut data = Vec::new();
+        write_message(&mut data, Framing::ContentLength, "{\"id\":1}").unwrap();
+        write_message(&mut data, Framing::ContentLength, "[\"é\"]").unwrap();
+        data.extend_from_slice(b"Content-Type: application/vscode-jsonrpc; charset=utf-8\r\ncontent-length: 2\r\n\r\n{}");
+        assert!(data.starts_with(b"Content-Length: 8\r\n\r\n{\"id\":1}Content-Length: 6\r\n\r\n"));
+
+        // Many messages in a single read
+        let mut reader = MessageReader::new(&data[..], Framing::ContentLength);
+        assert_eq!(reader.read_message().unwrap().unwrap(), "{\"id\":1}");
+        assert_eq!(reader.read_message().unwrap().unwrap(), "[\"é\"]");
+        assert_eq!(reader.read_message().unwrap().unwrap(), "{}");
+        assert!(reader.read_message().unwrap().is_none());
+
+        // Partial reads
+        let mut reader = MessageReader::new(BufReader::new(Trickle { data, position: 0 }), Framing::ContentLength);
+        assert_eq!(reader.read_message().unwrap().unwrap(), "{\"id\":1}");
+        assert_eq!(reader.read_message().unwrap().unwrap(), "[\"é\"]");
+        assert_eq!(reader.read_message().unwrap().unwrap(), "{}");
+        assert!(reader.read_message().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_content_length_errors() {
+        let data = b"Content-Length: 10\r\n\r\n0123456789Content-Length: 2\r\n\r\n{}Content-Length: x\r\n\r\n";
+        let mut reader = MessageReader::new(&data[..], Framing::ContentLength);
+        reader.set_max_size(5);
+        match reader.read_message() {
+            Err(FrameError::TooLarge(10)) => (),
+            _ => unreachable!()
+        }
+        assert_eq!(reader.read_message().unwrap().unwrap(), "{}");
+        match reader.read_message() {
+            Err(FrameError::InvalidHeader(h)) => assert_eq!(h, "Content-Length: x"),
+            _ => unreachable!()
+        }
+        let mut reader = MessageReader::new(&b"Content-Length: 10\r\n\r\n{}"[..], Framing::ContentLength);
+        match reader.read_message() {
+            Err(FrameError::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => (),
+            _ => unreachable!()
+        }
+    }
+
+    #[test]
+    fn test_newline() {
+        let data = b"{\"id\":1}\r\n\n0123456789\n[2]";
+        let mut reader = MessageReader::new(BufReader::new(Trickle { data: data.to_vec(), position: 0 }), Framing::Newline);
+        reader.set_max_size(9);
+        assert_eq!(reader.read_message().unwrap().unwrap(), "{\"id\":1}");
+        match reader.read_message() {
+            Err(FrameError::TooLarge(10)) => (),
+            _ => unreachable!()
+        }
+        assert_eq!(reader.read_message().unwrap().unwrap(), "[2]");
+        assert!(reader.read_message().unwrap().is_none());
+    }
+
+    #[derive(Clone)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf:&[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_serve() {
+        let rpc_server = Server::new();
+        rpc_server.register_method("Echo", |json_params| {
+            thread::sleep(Duration::from_millis(50));
+            Ok(json_params)
+        });
+        let mut input = Vec::new();
+        write_message(&mut input, Framing::ContentLength, "{\"jsonrpc\":\"2.0\",\"method\":\"Echo\",\"params\":[1],\"id\":1}").unwrap();
+        write_message(&mut input, Framing::ContentLength, "[1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20,21,22,23,24,25,26,27,28,29,30]").unwrap();
+        write_message(&mut input, Framing::ContentLength, "{\"jsonrpc\":\"2.0\",\"method\":\"Echo\",\"params\":[2],\"id\":2}").unwrap();
+        let output = Shared(Arc::new(Mutex::new(Vec::new())));
+        let mut reader = MessageReader::new(&input[..], Framing::ContentLength);
+        reader.set_max_size(64);
+        serve(reader, output.clone(), &rpc_server).unwrap();
+
+        let output = output.0.lock().unwrap().clone();
+        let mut reader = MessageReader::new(&output[..], Framing::ContentLength);
+        let mut responses = Vec::new();
+        while let Some(m) = reader.read_message().unwrap() { responses.push(Json::from_str(&m).unwrap()) }
+        assert_eq!(responses.len(), 3);
+        assert!(responses.contains(&Json::from_str("{\"jsonrpc\":\"2.0\",\"result\":[1],\"id\":1}").unwrap()));
+        assert!(responses.contains(&Json::from_str("{\"jsonrpc\":\"2.0\",\"result\":[2],\"id\":2}").unwrap()));
+        assert!(responses.iter().any(|r| r.find_path(&["error", "code"]) == Some(&Json::I64(-32600))));
+    }
+}
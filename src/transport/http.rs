@@ -0,0 +1,255 @@
+//! HTTP transport. Requires the feature **http**.
+//!
+//! Every POST body is a request or a batch. Notifications are answered with **204 No Content**.
+
+extern crate tiny_http;
+
+use std::cmp;
+use std::io::{self, Read};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use self::tiny_http::{Header, Method, Response, StatusCode};
+use super::super::{Context, Server};
+
+/// Default maximum size of a request body: 1 MiB.
+pub const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Default maximum number of requests executed at the same time.
+pub const MAX_CONCURRENCY: usize = 32;
+
+pub struct HttpServer {
+    http: tiny_http::Server,
+    rpc_server: Arc<Server>,
+    max_body_size: usize,
+    max_concurrency: usize,
+    slots: Arc<Slots>,
+    running: AtomicBool,
+}
+
+/// Number of requests being executed.
+struct Slots {
+    used: Mutex<usize>,
+    released: Condvar,
+}
+
+/// Request being executed, that frees its slot when dropped.
+struct Slot(Arc<Slots>);
+
+impl Slots {
+    fn acquire(slots:&Arc<Slots>, max:usize) -> Slot {
+        let mut used = slots.used.lock().unwrap();
+        while *used >= max { used = slots.released.wait(used).unwrap() }
+        *used += 1;
+        Slot(slots.clone())
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        *self.0.used.lock().unwrap() -= 1;
+        self.0.released.notify_one();
+    }
+}
+
+impl HttpServer {
+    pub fn bind<A>(addr:A, rpc_server:Arc<Server>) -> io::Result<HttpServer> where A: ToSocketAddrs {
+        let http = match tiny_http::Server::http(addr) {
+            Ok(s) => s,
+            Err(e) => return Err(io::Error::other(e))
+        };
+        Ok(HttpServer {
+            http, rpc_server,
+            max_body_size: MAX_BODY_SIZE,
+            max_concurrency: MAX_CONCURRENCY,
+            slots: Arc::new(Slots { used: Mutex::new(0), released: Condvar::new() }),
+            running: AtomicBool::new(true),
+        })
+    }
+
+    /// Bodies bigger than **max_body_size** bytes are rejected with **413 Payload Too Large**.
+    pub fn set_max_body_size(&mut self, max_body_size:usize) {
+        self.max_body_size = max_body_size;
+    }
+
+    /// At most **max_concurrency** requests are executed at the same time, each one in its own thread.
+    /// The next requests wait to be received until one of them is answered.
+    pub fn set_max_concurrency(&mut self, max_concurrency:usize) {
+        self.max_concurrency = cmp::max(max_concurrency, 1);
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Serves requests until **shutdown** is called. Each request is executed in its own thread,
+    /// up to **max_concurrency** at the same time.
+    pub fn run(&self) {
+        while self.running.load(Ordering::SeqCst) {
+            let slot = Slots::acquire(&self.slots, self.max_concurrency);
+            let request = match self.http.recv() {
+                Ok(r) => r,
+                Err(_) => continue
+            };
+            let rpc_server = self.rpc_server.clone();
+            let max_body_size = self.max_body_size;
+            thread::spawn(move || {
+                HttpServer::handle(request, &rpc_server, max_body_size);
+                // Moved into the thread to be freed once the request is answered
+                drop(slot);
+            });
+        }
+    }
+
+    /// Stops **run**. The requests already received are answered.
+    pub fn shutdown(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        self.http.unblock();
+    }
+
+    fn handle(mut request:tiny_http::Request, rpc_server:&Server, max_body_size:usize) {
+        if *request.method() != Method::Post {
+            let allow = Header::from_bytes(&b"Allow"[..], &b"POST"[..]).unwrap();
+            let _ = request.respond(Response::empty(405).with_header(allow));
+            return
+        }
+        let json_content = request.headers().iter().filter(|h| h.field.equiv("Content-Type")).all(|h| {
+            h.value.as_str().split(';').next().unwrap_or("").trim().eq_ignore_ascii_case("application/json")
+        });
+        if !json_content {
+            let _ = request.respond(Response::empty(415));
+            return
+        }
+        if request.body_length().unwrap_or(0) > max_body_size {
+            let _ = request.respond(Response::empty(413));
+            return
+        }
+        let mut body = Vec::new();
+        let read = request.as_reader().take(max_body_size as u64 + 1).read_to_end(&mut body);
+        if read.is_err() {
+            let _ = request.respond(Response::empty(400));
+            return
+        }
+        if body.len() > max_body_size {
+            let _ = request.respond(Response::empty(413));
+            return
+        }
+        let str_request = match String::from_utf8(body) {
+            Ok(s) => s,
+            Err(_) => {
+                let _ = request.respond(Response::empty(400));
+                return
+            }
+        };
+        let mut context = Context::new();
+        context.metadata_mut().insert("transport".to_string(), "http".to_string());
+        if let Some(addr) = request.remote_addr() { context.metadata_mut().insert("peer_addr".to_string(), addr.to_string()); }
+        let _ = match rpc_server.request_with_context(str_request, &context) {
+            Some(str_response) => {
+                let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+                request.respond(Response::from_string(str_response).with_header(content_type))
+            },
+            None => request.respond(Response::empty(StatusCode(204)))
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HttpServer;
+    use super::super::super::{Server, Json};
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    fn start(max_body_size:usize) -> (Arc<HttpServer>, SocketAddr) {
+        let rpc_server = Server::new();
+        rpc_server.register_method("Echo", Ok);
+        let mut http = HttpServer::bind("127.0.0.1:0", Arc::new(rpc_server)).unwrap();
+        http.set_max_body_size(max_body_size);
+        let http = Arc::new(http);
+        let addr = http.local_addr().unwrap();
+        let http_c = http.clone();
+        thread::spawn(move || http_c.run());
+        (http, addr)
+    }
+
+    fn send(addr:&SocketAddr, raw:&str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(raw.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn post(addr:&SocketAddr, content_type:&str, body:&str) -> String {
+        send(addr, &format!("POST / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+                            content_type, body.len(), body))
+    }
+
+    #[test]
+    fn test_post() {
+        let (http, addr) = start(1024);
+        let response = post(&addr, "application/json; charset=utf-8", "{\"jsonrpc\":\"2.0\",\"method\":\"Echo\",\"params\":[1],\"id\":1}");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.to_lowercase().contains("content-type: application/json"));
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        let data = Json::from_str(body).unwrap();
+        assert_eq!(data.find("result").unwrap(), &Json::from_str("[1]").unwrap());
+
+        let response = post(&addr, "application/json", "[{\"jsonrpc\":\"2.0\",\"method\":\"Echo\"},{\"jsonrpc\":\"2.0\",\"method\":\"Echo\"}]");
+        assert!(response.starts_with("HTTP/1.1 204"));
+        let response = post(&addr, "application/json", "{");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("-32700"));
+        http.shutdown();
+    }
+
+    #[test]
+    fn test_http_errors() {
+        let (http, addr) = start(64);
+        let response = send(&addr, "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 405"));
+        let response = post(&addr, "text/plain", "{\"jsonrpc\":\"2.0\",\"method\":\"Echo\",\"id\":1}");
+        assert!(response.starts_with("HTTP/1.1 415"));
+        let body = format!("{{\"jsonrpc\":\"2.0\",\"method\":\"Echo\",\"params\":[\"{}\"],\"id\":1}}", "x".repeat(100));
+        let response = post(&addr, "application/json", &body);
+        assert!(response.starts_with("HTTP/1.1 413"));
+        let response = send(&addr, "NOT HTTP\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 400"));
+        http.shutdown();
+    }
+
+    #[test]
+    fn test_max_concurrency() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let rpc_server = Server::new();
+        let (running_c, max_running_c) = (running.clone(), max_running.clone());
+        rpc_server.register_method("Work", move |_| {
+            let now = running_c.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running_c.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(100));
+            running_c.fetch_sub(1, Ordering::SeqCst);
+            Ok(Json::Boolean(true))
+        });
+        let mut http = HttpServer::bind("127.0.0.1:0", Arc::new(rpc_server)).unwrap();
+        http.set_max_concurrency(2);
+        let http = Arc::new(http);
+        let addr = http.local_addr().unwrap();
+        let http_c = http.clone();
+        thread::spawn(move || http_c.run());
+        let clients:Vec<_> = (0..6).map(|i| thread::spawn(move || {
+            post(&addr, "application/json", &format!("{{\"jsonrpc\":\"2.0\",\"method\":\"Work\",\"id\":{}}}", i))
+        })).collect();
+        for client in clients {
+            assert!(client.join().unwrap().starts_with("HTTP/1.1 200"));
+        }
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+        http.shutdown();
+    }
+}
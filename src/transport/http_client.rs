@@ -0,0 +1,282 @@
+//! HTTP client transport. Requires the feature **http-client**.
+//!
+//! Every call is a POST. The connections are kept alive and reused by the following calls.
+
+extern crate ureq;
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+use super::super::{Client, Error, Json};
+
+/// Failure of a call made with **HttpClient**.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HttpError {
+    /// The server answered with an HTTP status other than 200 or 204, and this body.
+    Status(u16, String),
+    /// The request couldn't be sent or the response couldn't be read, timeouts included.
+    Transport(String),
+    /// The body doesn't have a response for every request.
+    InvalidResponse(String),
+    /// The server returned a JSON-RPC error, or the body isn't a valid JSON-RPC response.
+    Rpc(Error),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HttpError::Status(status, ref body) => write!(f, "HTTP status {}: {}", status, body),
+            HttpError::Transport(ref e) => write!(f, "HTTP transport error: {}", e),
+            HttpError::InvalidResponse(ref e) => write!(f, "Invalid response: {}", e),
+            HttpError::Rpc(ref e) => write!(f, "JSON-RPC error {}: {}", e.code(), e.message()),
+        }
+    }
+}
+
+pub struct HttpClient {
+    agent: ureq::Agent,
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+impl HttpClient {
+    pub fn new(url:&str) -> HttpClient {
+        HttpClient::with_timeout(url, None)
+    }
+
+    /// **timeout** limits the whole call: connecting, sending the request and reading the response.
+    pub fn with_timeout(url:&str, timeout:Option<Duration>) -> HttpClient {
+        let mut builder = ureq::AgentBuilder::new();
+        if let Some(timeout) = timeout { builder = builder.timeout(timeout) }
+        HttpClient {
+            agent: builder.build(),
+            url: url.to_string(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Adds a header to all the requests, for instance **Authorization**.
+    pub fn set_header(&mut self, name:&str, value:&str) {
+        self.headers.push((name.to_string(), value.to_string()));
+    }
+
+    /// Sends a request and waits for its result.
+    pub fn call(&self, method:&str, params:Json) -> Result<Json, HttpError> {
+        let mut results = self.batch(&[(method, params)])?;
+        results.remove(0).map_err(HttpError::Rpc)
+    }
+
+    /// Sends many requests in a single batch and waits for their results, in the same order.
+    pub fn batch(&self, calls:&[(&str, Json)]) -> Result<Vec<Result<Json, Error>>, HttpError> {
+        // The ids only need to be unique in each POST
+        let client = Client::new();
+        let results = Arc::new(Mutex::new(vec![None; calls.len()]));
+        let requests:Vec<String> = calls.iter().enumerate().map(|(i, &(method, ref params))| {
+            let results = results.clone();
+            client.request(method, params.clone(), move |res| results.lock().unwrap()[i] = Some(res))
+        }).collect();
+        let body = if calls.len() == 1 { requests[0].clone() } else { format!("[{}]", requests.join(",")) };
+        let response = match self.post(&body)? {
+            Some(r) => r,
+            None => return Err(HttpError::InvalidResponse("Empty response".to_string()))
+        };
+        // Also the errors returned by the server without id, as a parse error
+        client.response(response).map_err(HttpError::Rpc)?;
+        let results = results.lock().unwrap().clone();
+        results.into_iter().map(|r| r.ok_or_else(|| HttpError::InvalidResponse("Missing response".to_string()))).collect()
+    }
+
+    /// Sends a notification. The server doesn't return any result.
+    pub fn notify(&self, method:&str, params:Json) -> Result<(), HttpError> {
+        self.post(&Client::new().notification(method, params)).map(|_| ())
+    }
+
+    /// Like **call** without blocking: the request is sent from another thread.
+    pub fn call_async(self:&Arc<Self>, method:&str, params:Json) -> HttpCall<Json> {
+        let (http_client, method) = (self.clone(), method.to_string());
+        HttpCall::spawn(move || http_client.call(&method, params))
+    }
+
+    /// Like **batch** without blocking: the requests are sent from another thread.
+    pub fn batch_async(self:&Arc<Self>, calls:Vec<(String, Json)>) -> HttpCall<Vec<Result<Json, Error>>> {
+        let http_client = self.clone();
+        HttpCall::spawn(move || {
+            let calls:Vec<(&str, Json)> = calls.iter().map(|(m, p)| (m.as_str(), p.clone())).collect();
+            http_client.batch(&calls)
+        })
+    }
+
+    /// Returns the body of the response, or **None** for **204 No Content**.
+    fn post(&self, body:&str) -> Result<Option<String>, HttpError> {
+        let mut request = self.agent.post(&self.url).set("Content-Type", "application/json");
+        for (name, value) in &self.headers { request = request.set(name, value) }
+        let response = match request.send_string(body) {
+            Ok(r) => r,
+            Err(ureq::Error::Status(status, r)) => return Err(HttpError::Status(status, r.into_string().unwrap_or_default())),
+            Err(ureq::Error::Transport(t)) => return Err(HttpError::Transport(t.to_string()))
+        };
+        match response.status() {
+            204 => Ok(None),
+            200 => response.into_string().map(Some).map_err(|e| HttpError::Transport(e.to_string())),
+            status => Err(HttpError::Status(status, response.into_string().unwrap_or_default()))
+        }
+    }
+}
+
+struct CallState<T> {
+    result: Option<Result<T, HttpError>>,
+    waker: Option<Waker>,
+}
+
+/// Future of a call made with **call_async** or **batch_async**.
+pub struct HttpCall<T> {
+    state: Arc<Mutex<CallState<T>>>,
+}
+
+impl<T> HttpCall<T> where T: Send + 'static {
+    fn spawn<F>(f:F) -> HttpCall<T> where F: FnOnce() -> Result<T, HttpError> + Send + 'static {
+        let state = Arc::new(Mutex::new(CallState { result: None, waker: None }));
+        let state_c = state.clone();
+        thread::spawn(move || {
+            let result = f();
+            let mut state = state_c.lock().unwrap();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() { waker.wake() }
+        });
+        HttpCall { state }
+    }
+
+    /// Blocks until the call has finished.
+    pub fn wait(self) -> Result<T, HttpError> {
+        let (tx, rx) = mpsc::channel();
+        let state = self.state.clone();
+        {
+            let mut state = state.lock().unwrap();
+            if let Some(result) = state.result.take() { return result }
+            state.waker = Some(Waker::from(Arc::new(ChannelWaker(Mutex::new(tx)))));
+        }
+        let _ = rx.recv();
+        let result = state.lock().unwrap().result.take();
+        result.unwrap_or_else(|| Err(HttpError::Transport("Call not finished".to_string())))
+    }
+}
+
+impl<T> Future for HttpCall<T> {
+    type Output = Result<T, HttpError>;
+
+    fn poll(self:Pin<&mut Self>, cx:&mut Context) -> Poll<Result<T, HttpError>> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+struct ChannelWaker(Mutex<mpsc::Sender<()>>);
+
+impl ::std::task::Wake for ChannelWaker {
+    fn wake(self:Arc<Self>) {
+        let _ = self.0.lock().unwrap().send(());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HttpClient, HttpError};
+    use super::super::super::{Json, Server};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{mpsc, Arc};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    /// HTTP server that answers the requests of each connection with **rpc_server**,
+    /// and sends the headers received to **tx**. Returns the url and the number of connections accepted.
+    fn start(rpc_server:Server, tx:mpsc::Sender<Vec<String>>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let connections_c = connections.clone();
+        let rpc_server = Arc::new(rpc_server);
+        thread::spawn(move || for stream in listener.incoming() {
+            connections_c.fetch_add(1, Ordering::SeqCst);
+            let mut stream = BufReader::new(stream.unwrap());
+            let (rpc_server, tx) = (rpc_server.clone(), tx.clone());
+            thread::spawn(move || loop {
+                let mut headers = Vec::new();
+                let mut line = String::new();
+                while stream.read_line(&mut line).unwrap_or(0) > 0 && line.trim() != "" {
+                    headers.push(line.trim().to_string());
+                    line.clear();
+                }
+                if headers.is_empty() { break }
+                let length:usize = headers.iter().filter_map(|h| h.strip_prefix("Content-Length: ")).next().unwrap().parse().unwrap();
+                let mut body = vec![0u8; length];
+                stream.read_exact(&mut body).unwrap();
+                tx.send(headers).unwrap();
+                let response = match rpc_server.request(String::from_utf8(body).unwrap()) {
+                    Some(r) => format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", r.len(), r),
+                    None => "HTTP/1.1 204 No Content\r\n\r\n".to_string()
+                };
+                stream.get_mut().write_all(response.as_bytes()).unwrap();
+            });
+        });
+        (url, connections)
+    }
+
+    #[test]
+    fn test_calls() {
+        let rpc_server = Server::new();
+        rpc_server.register_method("Echo", Ok);
+        let (tx, rx) = mpsc::channel();
+        let (url, connections) = start(rpc_server, tx);
+        let mut http_client = HttpClient::new(&url);
+        http_client.set_header("Authorization", "Bearer token");
+        assert_eq!(http_client.call("Echo", Json::from_str("[1]").unwrap()), Ok(Json::from_str("[1]").unwrap()));
+        assert!(rx.recv().unwrap().contains(&"Authorization: Bearer token".to_string()));
+        match http_client.call("Missing", Json::Null) {
+            Err(HttpError::Rpc(e)) => assert_eq!(e.code(), -32601),
+            _ => unreachable!()
+        }
+        let results = http_client.batch(&[("Echo", Json::from_str("[2]").unwrap()), ("Missing", Json::Null)]).unwrap();
+        assert_eq!(results[0], Ok(Json::from_str("[2]").unwrap()));
+        assert_eq!(results[1].clone().unwrap_err().code(), -32601);
+        assert_eq!(http_client.notify("Echo", Json::Null), Ok(()));
+
+        let http_client = Arc::new(http_client);
+        assert_eq!(http_client.call_async("Echo", Json::from_str("[3]").unwrap()).wait(), Ok(Json::from_str("[3]").unwrap()));
+        assert_eq!(rx.try_iter().count(), 4);
+        // All the calls used the same connection
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_http_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let mut streams = listener.incoming();
+            let mut stream = streams.next().unwrap().unwrap();
+            let _ = stream.read(&mut [0; 1024]);
+            stream.write_all(b"HTTP/1.1 401 Unauthorized\r\nContent-Length: 6\r\n\r\nDenied").unwrap();
+            // Never answers
+            let _stream = streams.next();
+            thread::sleep(Duration::from_secs(2));
+        });
+        let http_client = HttpClient::with_timeout(&url, Some(Duration::from_millis(200)));
+        assert_eq!(http_client.call("Echo", Json::Null), Err(HttpError::Status(401, "Denied".to_string())));
+        match http_client.call("Echo", Json::Null) {
+            Err(HttpError::Transport(_)) => (),
+            _ => unreachable!()
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! In-memory transport to test services end to end without sockets.
+//! The hooks of each direction can delay, drop, hold and corrupt the messages.
+
+use std::io;
+use std::mem;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use super::connection::{self, Connection, Sink};
+use super::super::{Client, Error, Json, Server};
+
+/// What a hook does with a message sent.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault {
+    Deliver,
+    /// Delivers the message after a while, from another thread.
+    Delay(Duration),
+    Drop,
+    /// Keeps the message until it's taken with **take_held**, to deliver it later in any order.
+    Hold,
+    /// Delivers another message instead, for instance with corrupted bytes.
+    Replace(String),
+}
+
+type Hook = Box<dyn Fn(&str) -> Fault + Send>;
+
+struct SinkInner {
+    tx: Mutex<mpsc::Sender<String>>,
+    hook: Mutex<Option<Hook>>,
+    held: Mutex<Vec<String>>,
+}
+
+/// Outgoing side of a **MemoryConnection**. The hook is shared by all its clones.
+#[derive(Clone)]
+pub struct MemorySink {
+    inner: Arc<SinkInner>,
+}
+
+impl MemorySink {
+    /// **f** decides what happens with every message sent from now on.
+    pub fn set_hook<F>(&self, f:F) where F: Fn(&str) -> Fault + Send + 'static {
+        *self.inner.hook.lock().unwrap() = Some(Box::new(f));
+    }
+
+    pub fn clear_hook(&self) {
+        *self.inner.hook.lock().unwrap() = None;
+    }
+
+    /// Returns the messages held, in the order they were sent.
+    pub fn take_held(&self) -> Vec<String> {
+        mem::take(&mut *self.inner.held.lock().unwrap())
+    }
+
+    /// Delivers a message without calling the hook.
+    pub fn deliver(&self, message:String) -> io::Result<()> {
+        self.inner.tx.lock().unwrap().send(message).map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+}
+
+impl Sink for MemorySink {
+    fn send(&self, message:String) -> io::Result<()> {
+        let fault = match *self.inner.hook.lock().unwrap() {
+            Some(ref f) => f(&message),
+            None => Fault::Deliver
+        };
+        match fault {
+            Fault::Deliver => self.deliver(message),
+            Fault::Delay(duration) => {
+                let sink = self.clone();
+                thread::spawn(move || {
+                    thread::sleep(duration);
+                    let _ = sink.deliver(message);
+                });
+                Ok(())
+            },
+            Fault::Drop => Ok(()),
+            Fault::Hold => {
+                self.inner.held.lock().unwrap().push(message);
+                Ok(())
+            },
+            Fault::Replace(other) => self.deliver(other),
+        }
+    }
+}
+
+/// End of an in-memory connection. It's closed when all the sinks of the other end are dropped.
+pub struct MemoryConnection {
+    incoming: mpsc::Receiver<String>,
+    sink: MemorySink,
+}
+
+impl Connection for MemoryConnection {
+    type Sink = MemorySink;
+
+    fn receive(&mut self) -> Option<String> {
+        self.incoming.recv().ok()
+    }
+
+    fn sink(&self) -> MemorySink {
+        self.sink.clone()
+    }
+}
+
+/// Returns both ends of an in-memory connection.
+pub fn pair() -> (MemoryConnection, MemoryConnection) {
+    let (tx_a, rx_a) = mpsc::channel();
+    let (tx_b, rx_b) = mpsc::channel();
+    let sink = |tx| MemorySink {
+        inner: Arc::new(SinkInner { tx: Mutex::new(tx), hook: Mutex::new(None), held: Mutex::new(Vec::new()) })
+    };
+    (MemoryConnection { incoming: rx_a, sink: sink(tx_b) }, MemoryConnection { incoming: rx_b, sink: sink(tx_a) })
+}
+
+/// **Client** connected to a **Server** in memory.
+pub struct Loopback {
+    client: Arc<Client>,
+    requests: MemorySink,
+    responses: MemorySink,
+}
+
+impl Loopback {
+    /// Serves **rpc_server** in a new thread until the loopback is dropped.
+    pub fn new(rpc_server:Arc<Server>) -> Loopback {
+        let (client_side, mut server_side) = pair();
+        let (requests, responses) = (client_side.sink, server_side.sink());
+        thread::spawn(move || connection::serve(&mut server_side, &rpc_server));
+        let client = Arc::new(Client::new());
+        let client_c = client.clone();
+        // The reader only has the receiver, so dropping **requests** closes the server side
+        let incoming = client_side.incoming;
+        thread::spawn(move || {
+            while let Ok(message) = incoming.recv() { let _ = client_c.response(message); }
+        });
+        Loopback { client, requests, responses }
+    }
+
+    /// Messages from the client to the server.
+    pub fn requests(&self) -> &MemorySink {
+        &self.requests
+    }
+
+    /// Messages from the server to the client.
+    pub fn responses(&self) -> &MemorySink {
+        &self.responses
+    }
+
+    /// Sends a request. **f_response** is called with the result once the response is received.
+    pub fn request<F>(&self, method:&str, params:Json, f_response:F) where F: FnOnce(Result<Json,Error>) + Send + 'static {
+        let _ = self.requests.send(self.client.request(method, params, f_response));
+    }
+
+    /// Sends a request and waits for its result. It doesn't return if the request or the response are dropped.
+    pub fn call(&self, method:&str, params:Json) -> Result<Json,Error> {
+        let (tx, rx) = mpsc::channel();
+        self.request(method, params, move |res| { let _ = tx.send(res); });
+        match rx.recv() {
+            Ok(res) => res,
+            Err(_) => Err(Error::predefined(-32603, None))
+        }
+    }
+
+    pub fn notify(&self, method:&str, params:Json) {
+        let _ = self.requests.send(self.client.notification(method, params));
+    }
+
+    /// Number of calls waiting for a response.
+    pub fn pending(&self) -> usize {
+        self.client.pending()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Fault, Loopback};
+    use super::super::connection::Sink;
+    use super::super::super::{Server, Json};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn loopback() -> Loopback {
+        let rpc_server = Server::new();
+        rpc_server.register_method("Echo", Ok);
+        Loopback::new(Arc::new(rpc_server))
+    }
+
+    #[test]
+    fn test_call() {
+        let loopback = loopback();
+        assert_eq!(loopback.call("Echo", Json::from_str("[1]").unwrap()), Ok(Json::from_str("[1]").unwrap()));
+        assert_eq!(loopback.call("Missing", Json::Null).unwrap_err().code(), -32601);
+        assert_eq!(loopback.pending(), 0);
+
+        loopback.responses().set_hook(|_| Fault::Delay(Duration::from_millis(100)));
+        let start = Instant::now();
+        assert_eq!(loopback.call("Echo", Json::from_str("[2]").unwrap()), Ok(Json::from_str("[2]").unwrap()));
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_reorder() {
+        let loopback = loopback();
+        loopback.responses().set_hook(|_| Fault::Hold);
+        let (tx, rx) = mpsc::channel();
+        for i in 1..4 {
+            let tx = tx.clone();
+            loopback.request("Echo", Json::Array(vec![Json::U64(i)]), move |res| tx.send((i, res)).unwrap());
+        }
+        let mut held = Vec::new();
+        while held.len() < 3 {
+            held.extend(loopback.responses().take_held());
+            thread::sleep(Duration::from_millis(1));
+        }
+        for response in held.into_iter().rev() { loopback.responses().deliver(response).unwrap() }
+        for _ in 0..3 {
+            let (i, res) = rx.recv().unwrap();
+            assert_eq!(res, Ok(Json::Array(vec![Json::U64(i)])));
+        }
+        assert_eq!(loopback.pending(), 0);
+    }
+
+    #[test]
+    fn test_drop_and_corrupt() {
+        let loopback = loopback();
+        loopback.requests().set_hook(|m| if m.contains("Dropped") { Fault::Drop } else { Fault::Deliver });
+        loopback.request("Dropped", Json::Null, |_| unreachable!());
+        assert_eq!(loopback.pending(), 1);
+
+        // A corrupted request is answered with a parse error without id
+        loopback.requests().set_hook(|m| Fault::Replace(m[1..].to_string()));
+        let (tx, rx) = mpsc::channel();
+        loopback.responses().set_hook(move |m| { tx.send(m.to_string()).unwrap(); Fault::Drop });
+        loopback.requests().send("{\"jsonrpc\":\"2.0\",\"method\":\"Echo\",\"params\":[],\"id\":1}".to_string()).unwrap();
+        let response = Json::from_str(&rx.recv().unwrap()).unwrap();
+        assert_eq!(response.find_path(&["error", "code"]), Some(&Json::I64(-32700)));
+        assert_eq!(response.find("id"), Some(&Json::Null));
+        assert_eq!(loopback.pending(), 1);
+    }
+
+    #[test]
+    fn test_drop() {
+        let rpc_server = Server::new();
+        rpc_server.register_method("Echo", Ok);
+        let rpc_server = Arc::new(rpc_server);
+        let loopback = Loopback::new(rpc_server.clone());
+        assert_eq!(loopback.call("Echo", Json::Null), Ok(Json::Null));
+        assert_eq!(Arc::strong_count(&rpc_server), 2);
+        // The thread of connection::serve ends and releases the server
+        drop(loopback);
+        let start = Instant::now();
+        while Arc::strong_count(&rpc_server) > 1 {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
@@ -0,0 +1,16 @@
+//! Transports that expose a **Server** over different channels.
+
+pub mod connection;
+pub mod framing;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "http-client")]
+pub mod http_client;
+pub mod memory;
+pub mod stdio;
+pub mod tcp;
+#[cfg(target_os = "linux")]
+pub mod unix;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+mod stream;
@@ -0,0 +1,130 @@
+//! Stdio transport to run a **Server** as a subprocess driven by its parent.
+//! The handlers mustn't print to stdout, it's used for the responses.
+
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+use super::framing::{self, FrameError, Framing, MessageReader};
+use super::super::{Context, Server};
+
+pub struct StdioServer {
+    rpc_server: Arc<Server>,
+    framing: Framing,
+    max_message_size: usize,
+}
+
+impl StdioServer {
+    pub fn new(rpc_server:Arc<Server>) -> StdioServer {
+        StdioServer {
+            rpc_server,
+            framing: Framing::Newline,
+            max_message_size: framing::MAX_MESSAGE_SIZE,
+        }
+    }
+
+    /// By default **Framing::Newline**.
+    pub fn set_framing(&mut self, framing:Framing) {
+        self.framing = framing;
+    }
+
+    pub fn set_max_message_size(&mut self, max_message_size:usize) {
+        self.max_message_size = max_message_size;
+    }
+
+    /// Reads stdin until EOF and writes the responses to stdout.
+    /// Returns once the calls in flight have been answered.
+    pub fn run(&self) -> Result<(), FrameError> {
+        let stdin = io::stdin();
+        self.run_with(stdin.lock(), io::stdout())
+    }
+
+    /// Like **run**, with other streams instead of stdin and stdout.
+    pub fn run_with<R, W>(&self, reader:R, writer:W) -> Result<(), FrameError> where R: BufRead, W: Write + Send + 'static {
+        let mut reader = MessageReader::new(reader, self.framing);
+        reader.set_max_size(self.max_message_size);
+        let mut context = Context::new();
+        context.metadata_mut().insert("transport".to_string(), "stdio".to_string());
+        framing::serve_with_context(reader, writer, context, &self.rpc_server)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::StdioServer;
+    use super::super::framing::{FrameError, Framing};
+    use super::super::super::{Server, Json};
+    use std::io::{self, Cursor, Write};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    /// Stdout that takes a byte at a time, so the responses would be mixed if they weren't written atomically.
+    #[derive(Clone)]
+    struct SlowStdout(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SlowStdout {
+        fn write(&mut self, buf:&[u8]) -> io::Result<usize> {
+            thread::yield_now();
+            self.0.lock().unwrap().push(buf[0]);
+            Ok(1)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn stdio_server(framing:Framing) -> StdioServer {
+        let rpc_server = Server::new();
+        rpc_server.register_method("Wait", |json_params| {
+            let ms = json_params.as_array().unwrap()[0].as_u64().unwrap();
+            thread::sleep(Duration::from_millis(ms));
+            Ok(Json::String(format!("waited {} ms", ms)))
+        });
+        rpc_server.register_method_with_context("Transport", |context, _| Ok(Json::String(context.metadata()["transport"].clone())));
+        let mut stdio_server = StdioServer::new(Arc::new(rpc_server));
+        stdio_server.set_framing(framing);
+        stdio_server
+    }
+
+    #[test]
+    fn test_concurrent_calls() {
+        let mut stdin = String::new();
+        for (id, ms) in [300, 200, 100, 0, 100, 200].iter().enumerate() {
+            stdin.push_str(&format!("{{\"jsonrpc\":\"2.0\",\"method\":\"Wait\",\"params\":[{}],\"id\":{}}}\n", ms, id));
+        }
+        stdin.push_str("{\"jsonrpc\":\"2.0\",\"method\":\"Transport\",\"id\":6}\n");
+        let stdout = SlowStdout(Arc::new(Mutex::new(Vec::new())));
+        stdio_server(Framing::Newline).run_with(Cursor::new(stdin.into_bytes()), stdout.clone()).unwrap();
+
+        let output = String::from_utf8(stdout.0.lock().unwrap().clone()).unwrap();
+        let mut ids = Vec::new();
+        for line in output.lines() {
+            let response = Json::from_str(line).unwrap();
+            let id = response.find("id").unwrap().as_u64().unwrap();
+            let result = response.find("result").unwrap().as_string().unwrap().to_string();
+            if id == 6 { assert_eq!(result, "stdio") } else { assert!(result.starts_with("waited ")) }
+            ids.push(id);
+        }
+        ids.sort();
+        assert_eq!(ids, vec![0, 1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_eof_during_call() {
+        // The input ends in the middle of a message, while a call is in flight
+        let request = "{\"jsonrpc\":\"2.0\",\"method\":\"Wait\",\"params\":[200],\"id\":1}";
+        let stdin = format!("Content-Length: {}\r\n\r\n{}Content-Length: 60\r\n\r\n{{\"jsonrpc\"", request.len(), request);
+        let stdout = SlowStdout(Arc::new(Mutex::new(Vec::new())));
+        match stdio_server(Framing::ContentLength).run_with(Cursor::new(stdin.into_bytes()), stdout.clone()) {
+            Err(FrameError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            _ => unreachable!()
+        }
+
+        // A clean EOF between messages isn't an error
+        let stdin = format!("Content-Length: {}\r\n\r\n{}", request.len(), request);
+        assert!(stdio_server(Framing::ContentLength).run_with(Cursor::new(stdin.into_bytes()), io::sink()).is_ok());
+
+        let output = String::from_utf8(stdout.0.lock().unwrap().clone()).unwrap();
+        let body = "{\"id\":1,\"jsonrpc\":\"2.0\",\"result\":\"waited 200 ms\"}";
+        assert_eq!(output, format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+    }
+}
@@ -0,0 +1,200 @@
+//! Client and listener shared by the transports over streams.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use super::framing::{write_message, Framing, MessageReader};
+use super::super::{Client, Error, Json};
+
+type SendFn = Box<dyn Fn(String) -> io::Result<()> + Send + Sync>;
+
+/// Calls of a client over a connection. The messages are sent with a function,
+/// and the thread that reads the connection passes them to its **Responses**.
+pub struct ClientCore {
+    client: Arc<Client>,
+    closed: Arc<AtomicBool>,
+    send: SendFn,
+}
+
+/// Side of a **ClientCore** used by the thread that reads the connection.
+pub struct Responses {
+    client: Arc<Client>,
+    closed: Arc<AtomicBool>,
+}
+
+impl Responses {
+    pub fn response(&self, message:String) {
+        let _ = self.client.response(message);
+    }
+
+    /// The connection is closed: the calls still pending fail, and so do the next ones.
+    pub fn closed(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.client.cancel_pending(closed_error());
+    }
+}
+
+impl ClientCore {
+    pub fn new<F>(send:F) -> (ClientCore, Responses) where F: Fn(String) -> io::Result<()> + Send + Sync + 'static {
+        let client = Arc::new(Client::new());
+        let closed = Arc::new(AtomicBool::new(false));
+        let responses = Responses { client: client.clone(), closed: closed.clone() };
+        (ClientCore { client, closed, send: Box::new(send) }, responses)
+    }
+
+    pub fn request<F>(&self, method:&str, params:Json, f_response:F) -> io::Result<()> where F: FnOnce(Result<Json,Error>) + Send + 'static {
+        let str_request = self.client.request(method, params, f_response);
+        let res = (self.send)(str_request);
+        // The reader may have finished before the request was pending
+        if res.is_err() || self.closed.load(Ordering::SeqCst) { self.client.cancel_pending(closed_error()) }
+        res
+    }
+
+    pub fn call(&self, method:&str, params:Json) -> Result<Json,Error> {
+        let (tx, rx) = mpsc::channel();
+        let _ = self.request(method, params, move |res| { let _ = tx.send(res); });
+        match rx.recv() {
+            Ok(res) => res,
+            Err(_) => Err(closed_error())
+        }
+    }
+
+    pub fn notify(&self, method:&str, params:Json) -> io::Result<()> {
+        (self.send)(self.client.notification(method, params))
+    }
+}
+
+/// **ClientCore** over a byte stream with framing.
+pub struct StreamClient<W> {
+    core: ClientCore,
+    writer: Arc<Mutex<W>>,
+}
+
+impl<W> StreamClient<W> where W: Write + Send + 'static {
+    /// Reads the responses in a new thread until the end of **reader**. Then the calls still pending fail.
+    pub fn new<R>(reader:R, writer:W, framing:Framing) -> StreamClient<W> where R: Read + Send + 'static {
+        let writer = Arc::new(Mutex::new(writer));
+        let writer_c = writer.clone();
+        let (core, responses) = ClientCore::new(move |message| write_message(&mut *writer_c.lock().unwrap(), framing, &message));
+        thread::spawn(move || {
+            let mut reader = MessageReader::new(BufReader::new(reader), framing);
+            while let Ok(Some(message)) = reader.read_message() { responses.response(message) }
+            responses.closed();
+        });
+        StreamClient { core, writer }
+    }
+
+    pub fn request<F>(&self, method:&str, params:Json, f_response:F) -> io::Result<()> where F: FnOnce(Result<Json,Error>) + Send + 'static {
+        self.core.request(method, params, f_response)
+    }
+
+    pub fn call(&self, method:&str, params:Json) -> Result<Json,Error> {
+        self.core.call(method, params)
+    }
+
+    pub fn notify(&self, method:&str, params:Json) -> io::Result<()> {
+        self.core.notify(method, params)
+    }
+
+    pub fn writer(&self) -> &Mutex<W> {
+        &self.writer
+    }
+}
+
+fn closed_error() -> Error {
+    Error::predefined(-32603, Some(Json::String("Connection closed".to_string())))
+}
+
+/// Stream accepted by a listener.
+pub trait Socket: Sized + Send + 'static {
+    fn try_clone(&self) -> io::Result<Self>;
+    fn close(&self);
+}
+
+impl Socket for TcpStream {
+    fn try_clone(&self) -> io::Result<TcpStream> {
+        TcpStream::try_clone(self)
+    }
+    fn close(&self) {
+        let _ = self.shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(unix)]
+impl Socket for UnixStream {
+    fn try_clone(&self) -> io::Result<UnixStream> {
+        UnixStream::try_clone(self)
+    }
+    fn close(&self) {
+        let _ = self.shutdown(Shutdown::Both);
+    }
+}
+
+/// Accept loop of the servers, that keeps the open connections to close them on shutdown.
+pub struct Acceptor<S> {
+    running: AtomicBool,
+    connections: Arc<Mutex<BTreeMap<usize, S>>>,
+}
+
+impl<S> Acceptor<S> where S: Socket {
+    pub fn new() -> Acceptor<S> {
+        Acceptor {
+            running: AtomicBool::new(true),
+            connections: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// Accepts connections until **shutdown** is called. **f** prepares each connection
+    /// and returns what serves it in its own thread, or **None** to close it.
+    pub fn run<I, F, J>(&self, incoming:I, mut f:F) where I: Iterator<Item=io::Result<S>>, F: FnMut(S) -> Option<J>, J: FnOnce() + Send + 'static {
+        let mut next_connection = 0;
+        for stream in incoming {
+            if !self.running.load(Ordering::SeqCst) { break }
+            let stream = match stream {
+                Ok(s) => s,
+                Err(_) => continue
+            };
+            let registered = match stream.try_clone() {
+                Ok(s) => s,
+                Err(_) => continue
+            };
+            let job = match f(stream) {
+                Some(j) => j,
+                None => continue
+            };
+            let id = next_connection;
+            next_connection += 1;
+            {
+                let mut connections = self.connections.lock().unwrap();
+                if !self.running.load(Ordering::SeqCst) { break }
+                connections.insert(id, registered);
+            }
+            let connections = self.connections.clone();
+            thread::spawn(move || {
+                job();
+                connections.lock().unwrap().remove(&id);
+            });
+        }
+    }
+
+    /// Stops **run** once it's woken up, and closes all the connections.
+    pub fn shutdown(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        for stream in self.connections.lock().unwrap().values() { stream.close() }
+    }
+}
+
+/// Wakes up the accept of a TCP listener after **Acceptor::shutdown**.
+pub fn wake_up(listener:&TcpListener) {
+    if let Ok(mut addr) = listener.local_addr() {
+        if addr.ip().is_unspecified() {
+            addr.set_ip(if addr.is_ipv4() { [127, 0, 0, 1].into() } else { [0, 0, 0, 0, 0, 0, 0, 1].into() });
+        }
+        let _ = TcpStream::connect(addr);
+    }
+}
@@ -0,0 +1,173 @@
+//! TCP transport with one message per line, or with **Content-Length** framing.
+
+use std::io::{self, BufReader};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use super::framing::{self, Framing, MessageReader};
+use super::stream::{self, Acceptor, StreamClient};
+use super::super::{Context, Error, Json, Server};
+
+pub struct TcpServer {
+    listener: TcpListener,
+    rpc_server: Arc<Server>,
+    acceptor: Acceptor<TcpStream>,
+    framing: Framing,
+    max_message_size: usize,
+}
+
+impl TcpServer {
+    pub fn bind<A>(addr:A, rpc_server:Arc<Server>) -> io::Result<TcpServer> where A: ToSocketAddrs {
+        Ok(TcpServer {
+            listener: TcpListener::bind(addr)?,
+            rpc_server,
+            acceptor: Acceptor::new(),
+            framing: Framing::Newline,
+            max_message_size: framing::MAX_MESSAGE_SIZE,
+        })
+    }
+
+    /// By default **Framing::Newline**.
+    pub fn set_framing(&mut self, framing:Framing) {
+        self.framing = framing;
+    }
+
+    pub fn set_max_message_size(&mut self, max_message_size:usize) {
+        self.max_message_size = max_message_size;
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections until **shutdown** is called. Each connection is served in its own thread.
+    pub fn run(&self) {
+        self.acceptor.run(self.listener.incoming(), |writer| {
+            let reader = writer.try_clone().ok()?;
+            let mut context = Context::new();
+            context.metadata_mut().insert("transport".to_string(), "tcp".to_string());
+            if let Ok(addr) = writer.peer_addr() { context.metadata_mut().insert("peer_addr".to_string(), addr.to_string()); }
+            let rpc_server = self.rpc_server.clone();
+            let mut reader = MessageReader::new(BufReader::new(reader), self.framing);
+            reader.set_max_size(self.max_message_size);
+            Some(move || { let _ = framing::serve_with_context(reader, writer, context, &rpc_server); })
+        });
+    }
+
+    /// Stops **run** and closes all the connections.
+    pub fn shutdown(&self) {
+        self.acceptor.shutdown();
+        stream::wake_up(&self.listener);
+    }
+}
+
+/// Client side of the TCP transport. Many calls can be sent before receiving their responses.
+pub struct TcpClient {
+    inner: StreamClient<TcpStream>,
+}
+
+impl TcpClient {
+    pub fn connect<A>(addr:A) -> io::Result<TcpClient> where A: ToSocketAddrs {
+        TcpClient::connect_with_framing(addr, Framing::Newline)
+    }
+
+    pub fn connect_with_framing<A>(addr:A, framing:Framing) -> io::Result<TcpClient> where A: ToSocketAddrs {
+        let stream = TcpStream::connect(addr)?;
+        let reader = stream.try_clone()?;
+        Ok(TcpClient { inner: StreamClient::new(reader, stream, framing) })
+    }
+
+    /// Sends a request. **f_response** is called with the result once the response is received.
+    /// If the connection is closed, **f_response** is called with an "Internal error".
+    pub fn request<F>(&self, method:&str, params:Json, f_response:F) -> io::Result<()> where F: FnOnce(Result<Json,Error>) + Send + 'static {
+        self.inner.request(method, params, f_response)
+    }
+
+    /// Sends a request and waits for its result.
+    pub fn call(&self, method:&str, params:Json) -> Result<Json,Error> {
+        self.inner.call(method, params)
+    }
+
+    pub fn notify(&self, method:&str, params:Json) -> io::Result<()> {
+        self.inner.notify(method, params)
+    }
+
+    /// Closes the connection. The calls still pending fail.
+    pub fn close(&self) {
+        let _ = self.inner.writer().lock().unwrap().shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TcpClient, TcpServer};
+    use super::super::framing::Framing;
+    use super::super::super::{Server, Json};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::Duration;
+
+    fn start() -> (Arc<TcpServer>, thread::JoinHandle<()>) {
+        start_with_framing(Framing::Newline)
+    }
+
+    fn start_with_framing(framing:Framing) -> (Arc<TcpServer>, thread::JoinHandle<()>) {
+        let rpc_server = Server::new();
+        rpc_server.register_method_with_context("PeerAddr", |context, _| Ok(Json::String(context.metadata()["peer_addr"].clone())));
+        rpc_server.register_method("Wait", |json_params| {
+            let ms = json_params.as_array().unwrap()[0].as_u64().unwrap();
+            thread::sleep(Duration::from_millis(ms));
+            Ok(Json::U64(ms))
+        });
+        let mut tcp_server = TcpServer::bind("127.0.0.1:0", Arc::new(rpc_server)).unwrap();
+        tcp_server.set_framing(framing);
+        let tcp_server = Arc::new(tcp_server);
+        let tcp_server_c = tcp_server.clone();
+        let handle = thread::spawn(move || tcp_server_c.run());
+        (tcp_server, handle)
+    }
+
+    #[test]
+    fn test_call() {
+        let (tcp_server, _) = start();
+        let tcp_client = TcpClient::connect(tcp_server.local_addr().unwrap()).unwrap();
+        assert_eq!(tcp_client.call("Wait", Json::from_str("[0]").unwrap()), Ok(Json::U64(0)));
+        assert_eq!(tcp_client.call("Missing", Json::Null).unwrap_err().code(), -32601);
+        assert!(tcp_client.call("PeerAddr", Json::Null).unwrap().as_string().unwrap().starts_with("127.0.0.1:"));
+        tcp_server.shutdown();
+    }
+
+    #[test]
+    fn test_content_length() {
+        let (tcp_server, _) = start_with_framing(Framing::ContentLength);
+        let tcp_client = TcpClient::connect_with_framing(tcp_server.local_addr().unwrap(), Framing::ContentLength).unwrap();
+        assert_eq!(tcp_client.call("Wait", Json::from_str("[0]").unwrap()), Ok(Json::U64(0)));
+        assert_eq!(tcp_client.call("Wait", Json::from_str("[1]").unwrap()), Ok(Json::U64(1)));
+        tcp_server.shutdown();
+    }
+
+    #[test]
+    fn test_concurrent_calls() {
+        let (tcp_server, _) = start();
+        let tcp_client = TcpClient::connect(tcp_server.local_addr().unwrap()).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let tx_c = tx.clone();
+        tcp_client.request("Wait", Json::from_str("[300]").unwrap(), move |res| tx_c.send(res).unwrap()).unwrap();
+        tcp_client.notify("Wait", Json::from_str("[0]").unwrap()).unwrap();
+        tcp_client.request("Wait", Json::from_str("[0]").unwrap(), move |res| tx.send(res).unwrap()).unwrap();
+        assert_eq!(rx.recv().unwrap(), Ok(Json::U64(0)));
+        assert_eq!(rx.recv().unwrap(), Ok(Json::U64(300)));
+        tcp_client.close();
+        tcp_server.shutdown();
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let (tcp_server, handle) = start();
+        let tcp_client = TcpClient::connect(tcp_server.local_addr().unwrap()).unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || { handle.join().unwrap(); tx.send(()).unwrap() });
+        tcp_server.shutdown();
+        rx.recv_timeout(Duration::from_millis(1000)).unwrap();
+        assert_eq!(tcp_client.call("Wait", Json::from_str("[0]").unwrap()).unwrap_err().code(), -32603);
+    }
+}
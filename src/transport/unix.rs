@@ -0,0 +1,216 @@
+//! Unix domain socket transport, with the same framings as the TCP transport.
+//! The credentials of the peer can be used to choose the **Server** of each connection,
+//! and they're in the **Context** of the requests as metadata and as an extension.
+
+extern crate libc;
+
+use std::fs;
+use std::io::{self, BufReader};
+use std::mem;
+use std::net::Shutdown;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use super::framing::{self, Framing, MessageReader};
+use super::stream::{Acceptor, StreamClient};
+use super::super::{Context, Error, Json, Server};
+
+/// Credentials of the process at the other side of the socket, when it connected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PeerCredentials {
+    pub pid: i32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl PeerCredentials {
+    pub fn from_stream(stream:&UnixStream) -> io::Result<PeerCredentials> {
+        let mut ucred = libc::ucred { pid: 0, uid: 0, gid: 0 };
+        let mut size = mem::size_of::<libc::ucred>() as libc::socklen_t;
+        let res = unsafe {
+            libc::getsockopt(stream.as_raw_fd(), libc::SOL_SOCKET, libc::SO_PEERCRED, &mut ucred as *mut libc::ucred as *mut libc::c_void, &mut size)
+        };
+        if res != 0 { return Err(io::Error::last_os_error()) }
+        Ok(PeerCredentials { pid : ucred.pid, uid : ucred.uid, gid : ucred.gid })
+    }
+}
+
+type PeerServer = Box<dyn Fn(&PeerCredentials) -> Option<Arc<Server>> + Send + Sync>;
+
+pub struct UnixServer {
+    listener: UnixListener,
+    path: PathBuf,
+    rpc_server: Arc<Server>,
+    peer_server: Option<PeerServer>,
+    acceptor: Acceptor<UnixStream>,
+    framing: Framing,
+    max_message_size: usize,
+}
+
+impl UnixServer {
+    /// Creates the socket file at **path**. It fails if the file already exists.
+    pub fn bind<P>(path:P, rpc_server:Arc<Server>) -> io::Result<UnixServer> where P: AsRef<Path> {
+        Ok(UnixServer {
+            listener: UnixListener::bind(path.as_ref())?,
+            path: path.as_ref().to_path_buf(),
+            rpc_server,
+            peer_server: None,
+            acceptor: Acceptor::new(),
+            framing: Framing::Newline,
+            max_message_size: framing::MAX_MESSAGE_SIZE,
+        })
+    }
+
+    /// By default **Framing::Newline**.
+    pub fn set_framing(&mut self, framing:Framing) {
+        self.framing = framing;
+    }
+
+    pub fn set_max_message_size(&mut self, max_message_size:usize) {
+        self.max_message_size = max_message_size;
+    }
+
+    /// Chooses the **Server** of each connection from the credentials of the peer,
+    /// so its methods can capture them. The connection is closed when **f** returns **None**.
+    pub fn set_peer_server<F>(&mut self, f:F) where F: Fn(&PeerCredentials) -> Option<Arc<Server>> + Send + Sync + 'static {
+        self.peer_server = Some(Box::new(f));
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Accepts connections until **shutdown** is called. Each connection is served in its own thread.
+    pub fn run(&self) {
+        self.acceptor.run(self.listener.incoming(), |writer| {
+            let credentials = PeerCredentials::from_stream(&writer).ok();
+            let rpc_server = match self.peer_server {
+                Some(ref f) => credentials.as_ref().and_then(f)?,
+                None => self.rpc_server.clone()
+            };
+            let mut context = Context::new();
+            context.metadata_mut().insert("transport".to_string(), "unix".to_string());
+            if let Some(credentials) = credentials {
+                context.metadata_mut().insert("peer_pid".to_string(), credentials.pid.to_string());
+                context.metadata_mut().insert("peer_uid".to_string(), credentials.uid.to_string());
+                context.metadata_mut().insert("peer_gid".to_string(), credentials.gid.to_string());
+                context.extensions_mut().insert(credentials);
+            }
+            let reader = writer.try_clone().ok()?;
+            let mut reader = MessageReader::new(BufReader::new(reader), self.framing);
+            reader.set_max_size(self.max_message_size);
+            Some(move || { let _ = framing::serve_with_context(reader, writer, context, &rpc_server); })
+        });
+    }
+
+    /// Stops **run**, closes all the connections and removes the socket file.
+    pub fn shutdown(&self) {
+        self.acceptor.shutdown();
+        // Wakes up the accept of run
+        let _ = UnixStream::connect(&self.path);
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Client side of the Unix socket transport. Many calls can be sent before receiving their responses.
+pub struct UnixClient {
+    inner: StreamClient<UnixStream>,
+}
+
+impl UnixClient {
+    pub fn connect<P>(path:P) -> io::Result<UnixClient> where P: AsRef<Path> {
+        UnixClient::connect_with_framing(path, Framing::Newline)
+    }
+
+    pub fn connect_with_framing<P>(path:P, framing:Framing) -> io::Result<UnixClient> where P: AsRef<Path> {
+        let stream = UnixStream::connect(path)?;
+        let reader = stream.try_clone()?;
+        Ok(UnixClient { inner: StreamClient::new(reader, stream, framing) })
+    }
+
+    /// Sends a request. **f_response** is called with the result once the response is received.
+    /// If the connection is closed, **f_response** is called with an "Internal error".
+    pub fn request<F>(&self, method:&str, params:Json, f_response:F) -> io::Result<()> where F: FnOnce(Result<Json,Error>) + Send + 'static {
+        self.inner.request(method, params, f_response)
+    }
+
+    /// Sends a request and waits for its result.
+    pub fn call(&self, method:&str, params:Json) -> Result<Json,Error> {
+        self.inner.call(method, params)
+    }
+
+    pub fn notify(&self, method:&str, params:Json) -> io::Result<()> {
+        self.inner.notify(method, params)
+    }
+
+    /// Closes the connection. The calls still pending fail.
+    pub fn close(&self) {
+        let _ = self.inner.writer().lock().unwrap().shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PeerCredentials, UnixClient, UnixServer};
+    use super::super::framing::Framing;
+    use super::super::super::{Server, Json};
+    use std::env;
+    use std::path::PathBuf;
+    use std::process;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    static NEXT_SOCKET: AtomicUsize = AtomicUsize::new(0);
+
+    fn socket_path() -> PathBuf {
+        env::temp_dir().join(format!("json_rpc_{}_{}.sock", process::id(), NEXT_SOCKET.fetch_add(1, Ordering::SeqCst)))
+    }
+
+    fn start(unix_server:UnixServer) -> Arc<UnixServer> {
+        let unix_server = Arc::new(unix_server);
+        let unix_server_c = unix_server.clone();
+        thread::spawn(move || unix_server_c.run());
+        unix_server
+    }
+
+    #[test]
+    fn test_call() {
+        let rpc_server = Server::new();
+        rpc_server.register_method("Echo", Ok);
+        let mut unix_server = UnixServer::bind(socket_path(), Arc::new(rpc_server)).unwrap();
+        unix_server.set_framing(Framing::ContentLength);
+        let unix_server = start(unix_server);
+        let unix_client = UnixClient::connect_with_framing(unix_server.path(), Framing::ContentLength).unwrap();
+        assert_eq!(unix_client.call("Echo", Json::from_str("[1]").unwrap()), Ok(Json::from_str("[1]").unwrap()));
+        assert_eq!(unix_client.call("Missing", Json::Null).unwrap_err().code(), -32601);
+
+        unix_server.shutdown();
+        assert!(!unix_server.path().exists());
+        assert_eq!(unix_client.call("Echo", Json::from_str("[1]").unwrap()).unwrap_err().code(), -32603);
+    }
+
+    #[test]
+    fn test_peer_credentials() {
+        let mut unix_server = UnixServer::bind(socket_path(), Arc::new(Server::new())).unwrap();
+        unix_server.set_peer_server(|credentials:&PeerCredentials| {
+            if credentials.pid != process::id() as i32 { return None }
+            let credentials = *credentials;
+            let rpc_server = Server::new();
+            rpc_server.register_method("Uid", move |_| Ok(Json::U64(credentials.uid as u64)));
+            rpc_server.register_method_with_context("Pid", |context, _| {
+                let credentials = context.extensions().get::<PeerCredentials>().unwrap();
+                assert_eq!(context.metadata()["peer_pid"], credentials.pid.to_string());
+                Ok(Json::I64(credentials.pid as i64))
+            });
+            Some(Arc::new(rpc_server))
+        });
+        let unix_server = start(unix_server);
+        let unix_client = UnixClient::connect(unix_server.path()).unwrap();
+        let uid = unsafe { super::libc::getuid() };
+        assert_eq!(unix_client.call("Uid", Json::Null), Ok(Json::U64(uid as u64)));
+        assert_eq!(unix_client.call("Pid", Json::Null), Ok(Json::U64(process::id() as u64)));
+        unix_server.shutdown();
+    }
+}
@@ -0,0 +1,314 @@
+//! WebSocket transport. Each text frame is a request, a response or a batch.
+//! The server can also send notifications to the connected clients.
+
+extern crate tungstenite;
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use self::tungstenite::{Message, WebSocket};
+use self::tungstenite::client::IntoClientRequest;
+use self::tungstenite::protocol::Role;
+use serialize::json::ToJson;
+use super::connection::{self, Connection, Sink};
+use super::stream::{self, Acceptor, ClientCore};
+use super::super::{Context, Error, Id, Json, Request, Server};
+
+/// Stream of the WebSocket that reads. After the handshake, it doesn't write:
+/// all the frames are sent by the WebSocket that writes, so concurrent writers don't mix their bytes.
+struct ReadHalf {
+    stream: TcpStream,
+    write: bool,
+}
+
+impl Read for ReadHalf {
+    fn read(&mut self, buf:&mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for ReadHalf {
+    fn write(&mut self, buf:&[u8]) -> io::Result<usize> {
+        if self.write { self.stream.write(buf) } else { Ok(buf.len()) }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        if self.write { self.stream.flush() } else { Ok(()) }
+    }
+}
+
+/// Splits a WebSocket after its handshake into the one that reads and the one that writes.
+fn split(mut reader:WebSocket<ReadHalf>, role:Role) -> io::Result<(WebSocket<ReadHalf>, Mutex<WebSocket<TcpStream>>)> {
+    reader.get_mut().write = false;
+    let stream = reader.get_ref().stream.try_clone()?;
+    Ok((reader, Mutex::new(WebSocket::from_raw_socket(stream, role, None))))
+}
+
+fn to_io_error<E>(e:E) -> io::Error where E: ToString {
+    io::Error::other(e.to_string())
+}
+
+/// Returns the next text frame, or **None** once the WebSocket is closed. Pings and closes are answered with **writer**.
+fn read_text(reader:&mut WebSocket<ReadHalf>, writer:&Mutex<WebSocket<TcpStream>>) -> Option<String> {
+    loop {
+        match reader.read() {
+            Ok(Message::Text(text)) => return Some(text),
+            Ok(Message::Ping(data)) => { let _ = writer.lock().unwrap().send(Message::Pong(data)); },
+            Ok(Message::Close(_)) => {
+                let mut writer = writer.lock().unwrap();
+                if writer.close(None).is_ok() { let _ = writer.flush(); }
+            },
+            Ok(_) => (),
+            Err(_) => return None
+        }
+    }
+}
+
+/// Server side of a WebSocket, for the generic **connection::serve**.
+struct ServerConnection {
+    reader: WebSocket<ReadHalf>,
+    connection: Arc<WsConnection>,
+}
+
+impl Connection for ServerConnection {
+    type Sink = Arc<WsConnection>;
+
+    fn receive(&mut self) -> Option<String> {
+        read_text(&mut self.reader, &self.connection.writer)
+    }
+
+    fn sink(&self) -> Arc<WsConnection> {
+        self.connection.clone()
+    }
+
+    fn context(&self) -> Context {
+        let mut context = Context::new();
+        context.metadata_mut().insert("transport".to_string(), "websocket".to_string());
+        if let Ok(addr) = self.connection.peer_addr() { context.metadata_mut().insert("peer_addr".to_string(), addr.to_string()); }
+        context
+    }
+}
+
+impl Sink for Arc<WsConnection> {
+    fn send(&self, message:String) -> io::Result<()> {
+        WsConnection::send(self, message)
+    }
+}
+
+/// Connection of a client to a **WsServer**. It can be used to send notifications to the client.
+pub struct WsConnection {
+    writer: Mutex<WebSocket<TcpStream>>,
+    stream: TcpStream,
+}
+
+impl WsConnection {
+    pub fn notify(&self, method:&str, params:Json) -> io::Result<()> {
+        self.send(Request::new(method, params, Id::Absent).to_json().to_string())
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    pub fn close(&self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+
+    fn send(&self, text:String) -> io::Result<()> {
+        self.writer.lock().unwrap().send(Message::Text(text)).map_err(to_io_error)
+    }
+}
+
+type ConnectHook = Box<dyn Fn(Arc<WsConnection>) + Send + Sync>;
+
+pub struct WsServer {
+    listener: TcpListener,
+    rpc_server: Arc<Server>,
+    acceptor: Acceptor<TcpStream>,
+    connections: Arc<Mutex<BTreeMap<usize, Arc<WsConnection>>>>,
+    connect_hook: Option<Arc<ConnectHook>>,
+}
+
+impl WsServer {
+    pub fn bind<A>(addr:A, rpc_server:Arc<Server>) -> io::Result<WsServer> where A: ToSocketAddrs {
+        Ok(WsServer {
+            listener: TcpListener::bind(addr)?,
+            rpc_server,
+            acceptor: Acceptor::new(),
+            connections: Arc::new(Mutex::new(BTreeMap::new())),
+            connect_hook: None,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Calls **f** with every new connection, that can be kept to send notifications later.
+    pub fn set_connect_hook<F>(&mut self, f:F) where F: Fn(Arc<WsConnection>) + Send + Sync + 'static {
+        self.connect_hook = Some(Arc::new(Box::new(f)));
+    }
+
+    /// Sends a notification to all the connected clients.
+    pub fn broadcast(&self, method:&str, params:Json) {
+        let text = Request::new(method, params, Id::Absent).to_json().to_string();
+        let connections:Vec<Arc<WsConnection>> = self.connections.lock().unwrap().values().cloned().collect();
+        for connection in connections { let _ = connection.send(text.clone()); }
+    }
+
+    /// Accepts connections until **shutdown** is called. Each connection is served in its own thread.
+    pub fn run(&self) {
+        let mut next_connection = 0;
+        self.acceptor.run(self.listener.incoming(), |stream| {
+            let id = next_connection;
+            next_connection += 1;
+            let rpc_server = self.rpc_server.clone();
+            let connections = self.connections.clone();
+            let connect_hook = self.connect_hook.clone();
+            Some(move || {
+                let reader = match stream.try_clone() {
+                    Ok(s) => ReadHalf { stream: s, write: true },
+                    Err(_) => return
+                };
+                let (reader, writer) = match tungstenite::accept(reader).map_err(to_io_error).and_then(|ws| split(ws, Role::Server)) {
+                    Ok(ws) => ws,
+                    Err(_) => return
+                };
+                let connection = Arc::new(WsConnection { writer, stream });
+                connections.lock().unwrap().insert(id, connection.clone());
+                if let Some(f) = connect_hook { f(connection.clone()) }
+                connection::serve(&mut ServerConnection { reader, connection }, &rpc_server);
+                connections.lock().unwrap().remove(&id);
+            })
+        });
+    }
+
+    /// Stops **run** and closes all the connections.
+    pub fn shutdown(&self) {
+        self.acceptor.shutdown();
+        stream::wake_up(&self.listener);
+    }
+}
+
+/// Client side of the WebSocket transport. Many calls can be sent before receiving their responses.
+pub struct WsClient {
+    core: ClientCore,
+    writer: Arc<Mutex<WebSocket<TcpStream>>>,
+    stream: TcpStream,
+}
+
+impl WsClient {
+    /// Connects to a **ws://** url. The notifications sent by the server are ignored.
+    pub fn connect(url:&str) -> io::Result<WsClient> {
+        WsClient::connect_with_notifications(url, |_, _| ())
+    }
+
+    /// Connects to a **ws://** url. **on_notification** is called with the method and the params
+    /// of every notification sent by the server. It's called by the thread that reads the responses,
+    /// so it mustn't block: the responses aren't received until it returns.
+    pub fn connect_with_notifications<F>(url:&str, on_notification:F) -> io::Result<WsClient> where F: Fn(&str, Json) + Send + 'static {
+        let request = url.into_client_request().map_err(to_io_error)?;
+        let addr = {
+            let uri = request.uri();
+            if uri.scheme_str() != Some("ws") { return Err(io::Error::new(io::ErrorKind::InvalidInput, "Only ws:// urls are supported")) }
+            let host = uri.host().unwrap_or("").trim_start_matches('[').trim_end_matches(']').to_string();
+            (host, uri.port_u16().unwrap_or(80))
+        };
+        let stream = TcpStream::connect(addr)?;
+        let reader = ReadHalf { stream: stream.try_clone()?, write: true };
+        let (mut reader, writer) = match tungstenite::client(request, reader) {
+            Ok((ws, _)) => split(ws, Role::Client)?,
+            Err(e) => return Err(to_io_error(e))
+        };
+        let writer = Arc::new(writer);
+        let (writer_c, writer_r) = (writer.clone(), writer.clone());
+        let (core, responses) = ClientCore::new(move |text| writer_c.lock().unwrap().send(Message::Text(text)).map_err(to_io_error));
+        thread::spawn(move || {
+            while let Some(text) = read_text(&mut reader, &writer_r) {
+                if let Ok(Json::Object(ref message)) = Json::from_str(&text) {
+                    if let Some(Json::String(method)) = message.get("method") {
+                        on_notification(method, message.get("params").cloned().unwrap_or(Json::Null));
+                        continue
+                    }
+                }
+                responses.response(text);
+            }
+            responses.closed();
+        });
+        Ok(WsClient { core, writer, stream })
+    }
+
+    /// Sends a request. **f_response** is called with the result once the response is received.
+    /// If the connection is closed, **f_response** is called with an "Internal error".
+    pub fn request<F>(&self, method:&str, params:Json, f_response:F) -> io::Result<()> where F: FnOnce(Result<Json,Error>) + Send + 'static {
+        self.core.request(method, params, f_response)
+    }
+
+    /// Sends a request and waits for its result.
+    pub fn call(&self, method:&str, params:Json) -> Result<Json,Error> {
+        self.core.call(method, params)
+    }
+
+    pub fn notify(&self, method:&str, params:Json) -> io::Result<()> {
+        self.core.notify(method, params)
+    }
+
+    /// Closes the connection. The calls still pending fail.
+    pub fn close(&self) {
+        let _ = self.writer.lock().unwrap().close(None);
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{WsClient, WsServer};
+    use super::super::super::{Server, Json};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    fn start(mut ws_server:WsServer) -> Arc<WsServer> {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        ws_server.set_connect_hook(move |connection| { let _ = tx.lock().unwrap().send(connection); });
+        thread::spawn(move || for connection in rx { connection.notify("Welcome", Json::Null).unwrap() });
+        let ws_server = Arc::new(ws_server);
+        let ws_server_c = ws_server.clone();
+        thread::spawn(move || ws_server_c.run());
+        ws_server
+    }
+
+    #[test]
+    fn test_calls() {
+        let rpc_server = Server::new();
+        rpc_server.register_method("Wait", |json_params| {
+            let ms = json_params.as_array().unwrap()[0].as_u64().unwrap();
+            thread::sleep(Duration::from_millis(ms));
+            Ok(Json::U64(ms))
+        });
+        let ws_server = start(WsServer::bind("127.0.0.1:0", Arc::new(rpc_server)).unwrap());
+        let url = format!("ws://{}/", ws_server.local_addr().unwrap());
+        let (tx_n, rx_n) = mpsc::channel();
+        let tx_n = Mutex::new(tx_n);
+        let ws_client = WsClient::connect_with_notifications(&url, move |method, params| {
+            tx_n.lock().unwrap().send((method.to_string(), params)).unwrap();
+        }).unwrap();
+        assert_eq!(rx_n.recv().unwrap(), ("Welcome".to_string(), Json::Null));
+
+        let (tx, rx) = mpsc::channel();
+        let tx_c = tx.clone();
+        ws_client.request("Wait", Json::from_str("[300]").unwrap(), move |res| tx_c.send(res).unwrap()).unwrap();
+        ws_client.request("Wait", Json::from_str("[0]").unwrap(), move |res| tx.send(res).unwrap()).unwrap();
+        assert_eq!(rx.recv().unwrap(), Ok(Json::U64(0)));
+        assert_eq!(rx.recv().unwrap(), Ok(Json::U64(300)));
+        assert_eq!(ws_client.call("Missing", Json::Null).unwrap_err().code(), -32601);
+
+        ws_server.broadcast("News", Json::from_str("[1]").unwrap());
+        assert_eq!(rx_n.recv().unwrap(), ("News".to_string(), Json::from_str("[1]").unwrap()));
+
+        ws_server.shutdown();
+        assert_eq!(ws_client.call("Wait", Json::from_str("[0]").unwrap()).unwrap_err().code(), -32603);
+    }
+}
@@ -0,0 +1,121 @@
+//! Typed params and results with serde. Requires the feature **serde**.
+
+use std::collections::BTreeMap;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Number, Value};
+use super::{Error, Json, Server};
+
+/// Converts any serializable value into **Json**. Returns "Internal error" if it can't be serialized.
+pub fn to_json<T>(value:&T) -> Result<Json, Error> where T: Serialize {
+    match serde_json::to_value(value) {
+        Ok(v) => Ok(value_to_json(v)),
+        Err(e) => Err(Error::predefined(-32603, Some(Json::String(e.to_string()))))
+    }
+}
+
+/// Converts **Json** into any deserializable value. Structs are accepted by position (Array) or by name (Object).
+/// Returns "Invalid params" if it can't be deserialized.
+pub fn from_json<T>(json:Json) -> Result<T, Error> where T: DeserializeOwned {
+    match serde_json::from_value(json_to_value(json)) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(Error::predefined(-32602, Some(Json::String(e.to_string()))))
+    }
+}
+
+impl Server {
+    /// Registers a method with typed params and result.
+    /// **P** is deserialized from the params of the request, and **R** is serialized into the result.
+    pub fn register_typed_method<P, R, F>(&self, method:&str, f:F)
+        where P: DeserializeOwned, R: Serialize, F: Fn(P) -> Result<R,Error> + 'static + Send + Sync {
+        self.register_method(method, move |json_params| {
+            let params = from_json(json_params)?;
+            to_json(&f(params)?)
+        });
+    }
+}
+
+fn json_to_value(json:Json) -> Value {
+    match json {
+        Json::I64(v) => Value::Number(Number::from(v)),
+        Json::U64(v) => Value::Number(Number::from(v)),
+        Json::F64(v) => match Number::from_f64(v) {
+            Some(n) => Value::Number(n),
+            None => Value::Null
+        },
+        Json::String(v) => Value::String(v),
+        Json::Boolean(v) => Value::Bool(v),
+        Json::Array(v) => Value::Array(v.into_iter().map(json_to_value).collect()),
+        Json::Object(v) => Value::Object(v.into_iter().map(|(k, v)| (k, json_to_value(v))).collect::<Map<String, Value>>()),
+        Json::Null => Value::Null,
+    }
+}
+
+fn value_to_json(value:Value) -> Json {
+    match value {
+        Value::Number(v) => match (v.as_u64(), v.as_i64()) {
+            (Some(n), _) => Json::U64(n),
+            (None, Some(n)) => Json::I64(n),
+            _ => Json::F64(v.as_f64().unwrap_or(0f64))
+        },
+        Value::String(v) => Json::String(v),
+        Value::Bool(v) => Json::Boolean(v),
+        Value::Array(v) => Json::Array(v.into_iter().map(value_to_json).collect()),
+        Value::Object(v) => Json::Object(v.into_iter().map(|(k, v)| (k, value_to_json(v))).collect::<BTreeMap<String, Json>>()),
+        Value::Null => Json::Null,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Server, Error, Json};
+    use super::{from_json, to_json};
+
+    #[derive(Deserialize)]
+    struct Operands {
+        oper1: u64,
+        oper2: u64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Info {
+        amount: u32,
+        price: f64,
+        description: String,
+    }
+
+    #[test]
+    fn test_typed_method() {
+        let rpc_server = Server::new();
+        rpc_server.register_typed_method("Subtract", |p:Operands| {
+            if p.oper2 > p.oper1 { return Err(Error::custom(1, "Negative result", None)) }
+            Ok(p.oper1 - p.oper2)
+        });
+        rpc_server.register_typed_method("GetInfo", |_:()| {
+            Ok(Info { amount : 15, price: 2.33, description: "Apples".to_string() })
+        });
+        for str_request in &["{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":{\"oper1\":23, \"oper2\":4}, \"id\":1}",
+                             "{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":[23, 4], \"id\":1}"] {
+            let data = Json::from_str(&rpc_server.request(str_request.to_string()).unwrap()).unwrap();
+            assert_eq!(data.find("result").unwrap().as_u64().unwrap(), 19);
+        }
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":{\"oper1\":23}, \"id\":1}".to_string();
+        let data = Json::from_str(&rpc_server.request(str_request).unwrap()).unwrap();
+        assert_eq!(data.find_path(&["error", "code"]).unwrap().as_i64().unwrap(), -32602);
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"Subtract\", \"params\":[4, 23], \"id\":1}".to_string();
+        let data = Json::from_str(&rpc_server.request(str_request).unwrap()).unwrap();
+        assert_eq!(data.find_path(&["error", "code"]).unwrap().as_i64().unwrap(), 1);
+
+        let str_request = "{\"jsonrpc\":\"2.0\",\"method\":\"GetInfo\", \"id\":1}".to_string();
+        let data = Json::from_str(&rpc_server.request(str_request).unwrap()).unwrap();
+        let info:Info = from_json(data.find("result").unwrap().clone()).unwrap();
+        assert_eq!(info, Info { amount : 15, price: 2.33, description: "Apples".to_string() });
+    }
+
+    #[test]
+    fn test_conversions() {
+        let json = Json::from_str("{\"a\":[1,-2,2.5,true,null],\"b\":\"text\"}").unwrap();
+        let round_trip:Json = from_json::<::serde_json::Value>(json.clone()).and_then(|v| to_json(&v)).unwrap();
+        assert_eq!(round_trip, json);
+    }
+}